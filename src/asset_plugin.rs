@@ -1,5 +1,10 @@
+use crate::share::{type_to_colour, TileType};
+use crate::world_gen::{GenProfile, Prefab, GEN_PRESETS, PREFABS};
 use bevy::app::Plugin;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, Uuid};
 use std::collections::HashMap;
 
 pub struct AssetPlugin;
@@ -10,6 +15,18 @@ pub struct Animations(pub HashMap<String, Handle<AnimationClip>>);
 #[derive(Default)]
 pub struct Objects(pub HashMap<String, HandleUntyped>);
 
+/// One material for each kind of tile, shared by every chunk
+#[derive(Default)]
+pub struct TilePalette(pub HashMap<TileType, Handle<StandardMaterial>>);
+
+/// Handles to the world generation presets by name
+#[derive(Default)]
+pub struct GenPresets(pub HashMap<String, Handle<GenProfile>>);
+
+/// Handles to the hand-authored chunks by name
+#[derive(Default)]
+pub struct Prefabs(pub HashMap<String, Handle<Prefab>>);
+
 pub struct TriggerLoopAnimEvent(pub Entity, pub String);
 
 pub const MUSIC_TRACKS: u8 = 12;
@@ -17,17 +34,103 @@ pub const MUSIC_TRACKS: u8 = 12;
 impl Plugin for AssetPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Objects(HashMap::new()))
+            .init_resource::<TilePalette>()
+            .init_resource::<GenPresets>()
+            .add_asset::<GenProfile>()
+            .init_asset_loader::<GenProfileLoader>()
+            .init_resource::<Prefabs>()
+            .add_asset::<Prefab>()
+            .init_asset_loader::<PrefabLoader>()
             .add_event::<TriggerLoopAnimEvent>()
             .add_startup_system(load_assets.label("assets"))
             .add_system(loop_anim_handler);
     }
 }
 
+impl TypeUuid for GenProfile {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x6f1c_2a5e_93d4_4b8a_a1e7_0c5d_3f2b_9e41);
+}
+
+#[derive(Default)]
+struct GenProfileLoader;
+
+impl AssetLoader for GenProfileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let profile: GenProfile = serde_json::from_slice(bytes)?;
+            profile.check().map_err(anyhow::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(profile));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gen.json"]
+    }
+}
+
+impl TypeUuid for Prefab {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x2d8e_71b3_0c4f_4e96_b5a2_8f17_6e3c_d054);
+}
+
+#[derive(Default)]
+struct PrefabLoader;
+
+impl AssetLoader for PrefabLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let prefab: Prefab = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(prefab));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prefab.json"]
+    }
+}
+
+/// Pick up prefabs exported from the level editor, which can only be saved
+/// when running natively
+#[cfg(not(target_arch = "wasm32"))]
+fn load_saved_prefabs(asset_server: &AssetServer, prefabs: &mut Prefabs) {
+    let handles = match asset_server.load_folder("prefabs") {
+        Ok(handles) => handles,
+        Err(e) => {
+            warn!("Couldn't look for saved prefabs: {}", e);
+            return;
+        }
+    };
+    for handle in handles {
+        let name = asset_server.get_handle_path(&handle).and_then(|path| {
+            let file_name = path.path().file_name()?.to_str()?;
+            Some(file_name.strip_suffix(".prefab.json")?.to_string())
+        });
+        if let Some(name) = name {
+            prefabs.0.entry(name).or_insert_with(|| handle.typed());
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_saved_prefabs(_: &AssetServer, _: &mut Prefabs) {}
+
 fn load_assets(
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut objects: ResMut<Objects>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut palette: ResMut<TilePalette>,
+    mut gen_presets: ResMut<GenPresets>,
+    mut prefabs: ResMut<Prefabs>,
 ) {
     //Scenes
     let tree: Handle<Scene> = asset_server.load("tree.glb#Scene0");
@@ -65,6 +168,26 @@ fn load_assets(
     objects
         .0
         .insert("invisible".to_string(), invisible.clone_untyped());
+    for kind in TileType::ALL {
+        palette
+            .0
+            .insert(kind, materials.add(type_to_colour(&kind).into()));
+    }
+
+    // World generation
+    for name in GEN_PRESETS {
+        gen_presets.0.insert(
+            name.to_string(),
+            asset_server.load(format!("worldgen/{}.gen.json", name).as_str()),
+        );
+    }
+    for name in PREFABS {
+        prefabs.0.insert(
+            name.to_string(),
+            asset_server.load(format!("prefabs/{}.prefab.json", name).as_str()),
+        );
+    }
+    load_saved_prefabs(&asset_server, &mut prefabs);
 
     //Audio
     for i in 1..=MUSIC_TRACKS {
@@ -0,0 +1,199 @@
+//! Where chunks and tiles are in the world
+
+use crate::chunk_manager_plugin::{BLOCK_SIZE, FBLOCK_SIZE};
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+/// How many tiles there are along each side of a chunk
+const TILES: i64 = BLOCK_SIZE as i64;
+/// The first and last tiles in the world
+const FIRST_TILE: i64 = i32::MIN as i64 * TILES;
+const LAST_TILE: i64 = i32::MAX as i64 * TILES + TILES - 1;
+
+/// A chunk's position in the world, counted in chunks from the one that the
+/// game starts in.
+///
+/// The world stops at the edges of `i32`, so moving past them leaves you in
+/// the edge chunk rather than wrapping around.
+#[derive(Inspectable, Serialize, Deserialize, Default, Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct Chunk(pub i32, pub i32);
+
+impl Chunk {
+    /// The chunk that the game starts in
+    pub const ORIGIN: Chunk = Chunk(0, 0);
+
+    /// The chunk `dx` and `dz` chunks along from this one
+    pub fn offset(&self, dx: i32, dz: i32) -> Chunk {
+        Chunk(self.0.saturating_add(dx), self.1.saturating_add(dz))
+    }
+
+    /// How many chunks along each axis it is from this chunk to `other`
+    pub fn delta(&self, other: &Chunk) -> (i64, i64) {
+        (
+            other.0 as i64 - self.0 as i64,
+            other.1 as i64 - self.1 as i64,
+        )
+    }
+
+    /// How many chunks away from the start this chunk is
+    pub fn distance_from_origin(&self) -> f64 {
+        (self.0 as f64).hypot(self.1 as f64)
+    }
+
+    /// The position of one of this chunk's tiles in tiles from the start
+    pub fn to_tile(&self, x: usize, z: usize) -> (i64, i64) {
+        (
+            self.0 as i64 * TILES + x as i64,
+            self.1 as i64 * TILES + z as i64,
+        )
+    }
+
+    /// The chunk that a tile is in and where the tile is within it, with
+    /// tiles past the edge of the world belonging to the edge
+    pub fn from_tile(x: i64, z: i64) -> (Chunk, usize, usize) {
+        let (x, z) = (
+            x.clamp(FIRST_TILE, LAST_TILE),
+            z.clamp(FIRST_TILE, LAST_TILE),
+        );
+        (
+            Chunk(x.div_euclid(TILES) as i32, z.div_euclid(TILES) as i32),
+            x.rem_euclid(TILES) as usize,
+            z.rem_euclid(TILES) as usize,
+        )
+    }
+
+    /// The position of this chunk's corner relative to the floating origin,
+    /// which is at the corner of `current`
+    pub fn to_world(&self, current: &Chunk) -> Vec3 {
+        let (dx, dz) = current.delta(self);
+        Vec3::new(FBLOCK_SIZE * dx as f32, 0., FBLOCK_SIZE * dz as f32)
+    }
+
+    /// The chunk and tile under a position relative to the floating origin
+    pub fn from_world(current: &Chunk, pos: Vec3) -> (Chunk, usize, usize) {
+        let (x, z) = Chunk::tile_from_world(current, pos);
+        Chunk::from_tile(x, z)
+    }
+
+    /// The position in tiles from the start of the tile under a position
+    /// relative to the floating origin
+    pub fn tile_from_world(current: &Chunk, pos: Vec3) -> (i64, i64) {
+        let (x, z) = current.to_tile(0, 0);
+        (x + pos.x.round() as i64, z + pos.z.round() as i64)
+    }
+
+    /// The middle of a tile at floor level relative to the floating origin,
+    /// from its position in tiles from the start
+    pub fn tile_to_world(current: &Chunk, (x, z): (i64, i64)) -> Vec3 {
+        let (cx, cz) = current.to_tile(0, 0);
+        Vec3::new((x - cx) as f32, 0., (z - cz) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Chunk = Chunk(i32::MIN, i32::MIN);
+    const MAX: Chunk = Chunk(i32::MAX, i32::MAX);
+
+    #[test]
+    fn offsets_stop_at_the_edge_of_the_world() {
+        assert_eq!(Chunk::ORIGIN.offset(-3, 4), Chunk(-3, 4));
+        assert_eq!(MAX.offset(1, -1), Chunk(i32::MAX, i32::MAX - 1));
+        assert_eq!(MIN.offset(-1, 1), Chunk(i32::MIN, i32::MIN + 1));
+        assert_eq!(
+            Chunk(5, 5).offset(i32::MAX, i32::MIN),
+            Chunk(i32::MAX, i32::MIN + 5)
+        );
+    }
+
+    #[test]
+    fn deltas_span_the_whole_world() {
+        assert_eq!(Chunk(2, -1).delta(&Chunk(-1, 3)), (-3, 4));
+        assert_eq!(MIN.delta(&MAX), (u32::MAX as i64, u32::MAX as i64));
+        assert_eq!(MAX.delta(&MIN), (-(u32::MAX as i64), -(u32::MAX as i64)));
+    }
+
+    #[test]
+    fn distance_from_origin() {
+        assert_eq!(Chunk::ORIGIN.distance_from_origin(), 0.);
+        assert_eq!(Chunk(-3, 4).distance_from_origin(), 5.);
+    }
+
+    #[test]
+    fn tiles_round_trip() {
+        for chunk in [Chunk::ORIGIN, Chunk(-1, 1), Chunk(7, -12), MIN, MAX] {
+            for (x, z) in [(0, 0), (BLOCK_SIZE - 1, 0), (3, BLOCK_SIZE - 1)] {
+                let (tx, tz) = chunk.to_tile(x, z);
+                assert_eq!(Chunk::from_tile(tx, tz), (chunk, x, z));
+            }
+        }
+    }
+
+    #[test]
+    fn negative_tiles_belong_to_negative_chunks() {
+        assert_eq!(Chunk::from_tile(-1, 0), (Chunk(-1, 0), BLOCK_SIZE - 1, 0));
+        assert_eq!(
+            Chunk::from_tile(0, -(TILES + 1)),
+            (Chunk(0, -2), 0, BLOCK_SIZE - 1)
+        );
+        assert_eq!(Chunk::from_tile(-TILES, TILES), (Chunk(-1, 1), 0, 0));
+    }
+
+    #[test]
+    fn tiles_past_the_edge_belong_to_the_edge() {
+        assert_eq!(
+            Chunk::from_tile(i64::MIN, i64::MAX),
+            (Chunk(i32::MIN, i32::MAX), 0, BLOCK_SIZE - 1)
+        );
+        let (tx, tz) = MAX.to_tile(BLOCK_SIZE - 1, BLOCK_SIZE - 1);
+        assert_eq!(
+            Chunk::from_tile(tx + 1, tz + 1),
+            (MAX, BLOCK_SIZE - 1, BLOCK_SIZE - 1)
+        );
+    }
+
+    #[test]
+    fn world_positions_are_relative_to_the_current_chunk() {
+        let current = Chunk(3, -2);
+        assert_eq!(current.to_world(&current), Vec3::ZERO);
+        assert_eq!(
+            Chunk(4, -4).to_world(&current),
+            Vec3::new(FBLOCK_SIZE, 0., -2. * FBLOCK_SIZE)
+        );
+        assert_eq!(
+            Chunk::from_world(&current, Vec3::new(0.4, 3., -0.4)),
+            (current, 0, 0)
+        );
+        assert_eq!(
+            Chunk::from_world(&current, Vec3::new(-1., 0., FBLOCK_SIZE + 2.)),
+            (Chunk(2, -1), BLOCK_SIZE - 1, 2)
+        );
+    }
+
+    #[test]
+    fn tile_positions_follow_the_floating_origin() {
+        let (a, b) = (Chunk(-2, 5), Chunk(1, 4));
+        let pos = Vec3::new(3.2, 1., -7.6);
+        let tile = Chunk::tile_from_world(&a, pos);
+        assert_eq!(tile, (-2 * TILES + 3, 5 * TILES - 8));
+        assert_eq!(Chunk::tile_to_world(&a, tile), Vec3::new(3., 0., -8.));
+        // The same tile is somewhere else once the origin has moved
+        assert_eq!(
+            Chunk::tile_to_world(&b, tile),
+            Vec3::new(3. - 3. * FBLOCK_SIZE, 0., -8. + FBLOCK_SIZE)
+        );
+    }
+
+    #[test]
+    fn world_positions_round_trip_at_the_edges() {
+        for current in [MIN, MAX, Chunk::ORIGIN] {
+            for chunk in [current.offset(-1, 1), current.offset(1, -1), current] {
+                let corner = chunk.to_world(&current);
+                assert_eq!(Chunk::from_world(&current, corner), (chunk, 0, 0));
+            }
+        }
+    }
+}
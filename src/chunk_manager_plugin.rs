@@ -1,29 +1,38 @@
-use crate::asset_plugin::Objects;
-use crate::item_plugin::ItemType;
+use crate::asset_plugin::{GenPresets, Objects, Prefabs, TilePalette};
+use crate::chunk::Chunk;
+use crate::game_plugin::GameMode;
+use crate::item_plugin::ExplosionEvent;
 use crate::share::*;
-use crate::templates::entities_for_tile;
+use crate::streaming::StreamingPolicy;
+use crate::templates::{entities_for_tile, make_chunk_terrain};
+use crate::world_gen::{propogate_block, GenProfile, Prefab};
 use bevy::app::Plugin;
-use bevy::math::DVec2;
 use bevy::prelude::*;
-use bevy_inspector_egui::Inspectable;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
 use itertools::iproduct;
-use noise::{NoiseFn, Perlin, Seedable};
 
 use rand::Rng;
-use rand::SeedableRng;
-use std::collections::{HashMap, HashSet};
-
-pub const CHUNK_MID: Chunk = Chunk((u32::MAX / 2) as usize, (u32::MAX / 2) as usize);
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 
 pub const MAX_ELEV: usize = 5;
 
 pub const BLOCK_SIZE: usize = 20;
 pub const FBLOCK_SIZE: f32 = BLOCK_SIZE as f32;
 
-pub const RENDER_DISTANCE: isize = 2;
+/// The render distances that can be picked in the options
+pub const MIN_RENDER_DISTANCE: i32 = 1;
+pub const MAX_RENDER_DISTANCE: i32 = 5;
+
+/// How many chunks further than they were loaded that chunks are unloaded
+pub const STREAMING_HYSTERESIS: i32 = 1;
+
+/// How many levels an explosion digs out right where it goes off
+pub const BLAST_DEPTH: f32 = 3.;
 
-#[derive(Inspectable, Default, Hash, Eq, PartialEq, Clone, Debug)]
-pub struct Chunk(pub usize, pub usize);
+/// How many tiles may be spawned in a single frame
+pub const SPAWN_BUDGET: usize = 100;
 
 pub struct ChunkChangeEvent {
     pub oldchunk: Chunk,
@@ -31,29 +40,169 @@ pub struct ChunkChangeEvent {
 }
 pub struct SpawnBlockEvent {
     pub chunk: Chunk,
-    pub chunk_offset: (isize, isize),
 }
 
+/// Sent when part of a tile is destroyed or used up so that the change
+/// survives the chunk being unloaded
+pub struct TerrainChangeEvent {
+    pub chunk: Chunk,
+    pub tile: TileRef,
+}
+
+/// Sent when a tile has been changed outright, such as in the level editor,
+/// so that everything on it can be spawned again
+#[cfg_attr(not(build = "debug"), allow(dead_code))]
+pub struct TileEditedEvent {
+    pub chunk: Chunk,
+    pub x: usize,
+    pub z: usize,
+}
+
+/// Chunks being generated on the async compute pool
 #[derive(Default)]
-struct LoadedChunks(HashSet<Chunk>);
+struct GeneratingChunks(HashMap<Chunk, Task<Block>>);
+
+/// Generated chunks waiting to be spawned, along with the index of the next
+/// tile to spawn
+#[derive(Default)]
+struct SpawnQueue(VecDeque<(Chunk, usize)>);
+
+/// Chunks whose terrain meshes no longer match the world grid
+#[derive(Default)]
+struct DirtyChunks(HashSet<Chunk>);
 
 pub struct Seed(u32);
 
-struct WorldGrid(HashMap<Chunk, [[TileSettings; BLOCK_SIZE]; BLOCK_SIZE]>);
+pub type Block = [[TileSettings; BLOCK_SIZE]; BLOCK_SIZE];
 
-impl FromWorld for WorldGrid {
-    fn from_world(world: &mut World) -> Self {
-        let mut chunks = HashMap::new();
-        let seed = world
-            .get_resource::<Seed>()
-            .expect("Could not generate seed");
-        let origin = propogate_block(CHUNK_MID.0, CHUNK_MID.1, Some(seed.0));
-        chunks.insert(CHUNK_MID, origin);
+/// Changes made to a tile since it was generated
+#[derive(Default, Copy, Clone, Debug)]
+struct TileDelta {
+    lowered: usize,
+    copse_cleared: bool,
+    item_cleared: bool,
+}
 
-        WorldGrid(chunks)
+impl TileDelta {
+    fn record(&mut self, part: TilePart) {
+        match part {
+            TilePart::Block { lowered } => self.lowered += lowered,
+            TilePart::Copse => self.copse_cleared = true,
+            TilePart::Item => self.item_cleared = true,
+        }
+    }
+
+    fn apply(&self, tile: &mut TileSettings) {
+        if self.lowered > 0 {
+            tile.height = tile.height.saturating_sub(self.lowered);
+            if tile.height == 0 {
+                tile.kind = tile.biome.ground();
+            }
+        }
+        if self.copse_cleared {
+            tile.copse = false;
+        }
+        if self.item_cleared {
+            tile.item = None;
+        }
     }
 }
 
+/// The generated chunks along with the changes that have been made to them
+#[derive(Default)]
+pub struct WorldGrid {
+    generated: HashMap<Chunk, Block>,
+    deltas: HashMap<Chunk, HashMap<(usize, usize), TileDelta>>,
+    /// Chunks changed outright, which are kept when the world is rebuilt so
+    /// that edits that haven't been exported aren't lost
+    edited: HashSet<Chunk>,
+}
+
+impl WorldGrid {
+    /// The current state of a chunk if it has been generated
+    pub fn block(&self, chunk: &Chunk) -> Option<Block> {
+        let mut block = *self.generated.get(chunk)?;
+        if let Some(deltas) = self.deltas.get(chunk) {
+            for ((x, z), delta) in deltas.iter() {
+                delta.apply(&mut block[*x][*z]);
+            }
+        }
+        Some(block)
+    }
+
+    /// The current state of the tile under a position relative to the
+    /// floating origin
+    pub fn tile_at(&self, current_chunk: &Chunk, pos: Vec3) -> Option<TileSettings> {
+        let (chunk, x, z) = Chunk::from_world(current_chunk, pos);
+        self.tile(&chunk, x, z)
+    }
+
+    /// The current state of a single tile if its chunk has been generated
+    pub fn tile(&self, chunk: &Chunk, x: usize, z: usize) -> Option<TileSettings> {
+        let mut tile = self.generated.get(chunk)?[x][z];
+        if let Some(delta) = self.deltas.get(chunk).and_then(|d| d.get(&(x, z))) {
+            delta.apply(&mut tile);
+        }
+        Some(tile)
+    }
+
+    /// Change a tile outright, returning whether its chunk has been
+    /// generated.
+    ///
+    /// Any earlier changes are folded into the tile first so that `edit`
+    /// starts from what the player can see.
+    #[cfg_attr(not(build = "debug"), allow(dead_code))]
+    pub fn edit_tile(
+        &mut self,
+        chunk: &Chunk,
+        x: usize,
+        z: usize,
+        edit: impl FnOnce(&mut TileSettings),
+    ) -> bool {
+        let current = match self.tile(chunk, x, z) {
+            Some(tile) => tile,
+            None => return false,
+        };
+        if let Some(deltas) = self.deltas.get_mut(chunk) {
+            deltas.remove(&(x, z));
+        }
+        let tile = &mut self
+            .generated
+            .get_mut(chunk)
+            .expect("The chunk was just read")[x][z];
+        *tile = current;
+        edit(tile);
+        self.edited.insert(*chunk);
+        true
+    }
+}
+
+/// How many chunks away from the player the world is kept loaded
+pub struct RenderDistance(pub i32);
+
+impl Default for RenderDistance {
+    fn default() -> Self {
+        RenderDistance(2)
+    }
+}
+
+/// The name of the generation preset chosen in the options
+pub struct SelectedGenProfile(pub String);
+
+impl Default for SelectedGenProfile {
+    fn default() -> Self {
+        SelectedGenProfile("default".to_string())
+    }
+}
+
+/// The generation profile that new chunks are built with
+#[derive(Default)]
+pub struct ActiveGenProfile(pub GenProfile);
+
+/// The prefabs that have loaded, shared with the generation tasks
+#[derive(Default)]
+pub struct ActivePrefabs(pub Arc<Vec<Prefab>>);
+
 pub struct ChunkManagerPlugin;
 
 impl Plugin for ChunkManagerPlugin {
@@ -63,13 +212,42 @@ impl Plugin for ChunkManagerPlugin {
             rng.gen_range(0..u32::MAX)
         }))
         .init_resource::<WorldGrid>()
+        .init_resource::<SelectedGenProfile>()
+        .init_resource::<ActiveGenProfile>()
+        .init_resource::<ActivePrefabs>()
         .add_event::<ChunkChangeEvent>()
         .add_event::<SpawnBlockEvent>()
+        .add_event::<TerrainChangeEvent>()
+        .add_event::<TileEditedEvent>()
         .init_resource::<Chunk>()
-        .init_resource::<LoadedChunks>()
-        .add_system(handle_chunk_change)
+        .init_resource::<RenderDistance>()
+        .insert_resource(StreamingPolicy::new(
+            RenderDistance::default().0 - 1,
+            STREAMING_HYSTERESIS,
+        ))
+        .init_resource::<GeneratingChunks>()
+        .init_resource::<SpawnQueue>()
+        .init_resource::<DirtyChunks>()
+        .add_system(
+            handle_chunk_change
+                .label("chunk_change")
+                .after("player_location"),
+        )
+        .add_system(update_gen_profile)
+        .add_system(update_prefabs)
+        .add_system(regenerate_world.before("chunk_change"))
+        .add_system(apply_render_distance.before("chunk_change"))
         .add_system(handle_spawn_block)
-        .add_system(cull_far_entities);
+        .add_system(collect_generated_chunks)
+        .add_system(spawn_queued_tiles.after("chunk_change"))
+        .add_system(blast_terrain.before("terrain_change"))
+        .add_system(record_terrain_change.label("terrain_change"))
+        .add_system(refresh_edited_tiles.after("terrain_change"))
+        .add_system(
+            rebuild_dirty_chunks
+                .after("terrain_change")
+                .after("chunk_change"),
+        );
     }
 }
 
@@ -77,18 +255,18 @@ fn handle_chunk_change(
     mut ev_chunk_change: EventReader<ChunkChangeEvent>,
     mut ev_spawn_block: EventWriter<SpawnBlockEvent>,
     mut transforms: Query<(&mut Transform, Option<&mut OldLoc>, Option<&DynamicPos>)>,
-    mut loaded_chunks: ResMut<LoadedChunks>,
+    terrain: Query<(Entity, &Terrain)>,
+    mut streaming: ResMut<StreamingPolicy>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    render_distance: Res<RenderDistance>,
+    time: Res<Time>,
 ) {
     if let Some(ChunkChangeEvent { oldchunk, newchunk }) = ev_chunk_change.iter().last() {
         // Get shift ammount in chunks
         let (shx, shz) = {
-            let (ishx, ishz) = (
-                if oldchunk.0 > newchunk.0 { 1 } else { -1 }
-                    * ((oldchunk.0).abs_diff(newchunk.0) as isize),
-                if oldchunk.1 > newchunk.1 { 1 } else { -1 }
-                    * ((oldchunk.1).abs_diff(newchunk.1) as isize),
-            );
-
+            let (ishx, ishz) = newchunk.delta(oldchunk);
             ((ishx as f32) * FBLOCK_SIZE, (ishz as f32) * FBLOCK_SIZE)
         };
 
@@ -106,136 +284,370 @@ fn handle_chunk_change(
             }
         });
 
-        // Make sure that surrounding chunks are generated
-        // and spawn them
-        for (cx, cz) in iproduct!(-(RENDER_DISTANCE - 1)..=(RENDER_DISTANCE - 1), -0..=0) {
-            let chunk = Chunk(
-                newchunk.0.saturating_add_signed(cx),
-                newchunk.1.saturating_add_signed(cz),
-            );
-            if loaded_chunks.0.insert(chunk.clone()) {
-                ev_spawn_block.send(SpawnBlockEvent {
-                    chunk,
-                    chunk_offset: (cx, cz),
-                });
+        // Make sure that surrounding chunks are generated and spawned, and
+        // get rid of the ones that are now too far away
+        let lane_width = match *game_mode {
+            GameMode::Lane => 0,
+            GameMode::OpenWorld => render_distance.0 - 1,
+        };
+        let changes = streaming.update(newchunk, lane_width, time.seconds_since_startup());
+        for chunk in changes.load {
+            ev_spawn_block.send(SpawnBlockEvent { chunk });
+        }
+
+        if !changes.unload.is_empty() {
+            let unload: HashSet<Chunk> = changes.unload.into_iter().collect();
+            // Chunks that are still queued may not have any entities yet
+            spawn_queue.0.retain(|(chunk, _)| !unload.contains(chunk));
+            for (en, Terrain(chunk)) in terrain.iter() {
+                if unload.contains(chunk) {
+                    commands.entity(en).despawn_recursive();
+                }
             }
         }
     }
 }
 
-fn handle_spawn_block(
-    mut ev_spawn_block: EventReader<SpawnBlockEvent>,
-    mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    objects: Res<Objects>,
-    mut world_grid: ResMut<WorldGrid>,
-    seed: Res<Seed>,
+/// Load or unload chunks around the player when the render distance changes
+fn apply_render_distance(
+    render_distance: Res<RenderDistance>,
+    mut applied: Local<Option<i32>>,
+    mut streaming: ResMut<StreamingPolicy>,
+    current_chunk: Res<Chunk>,
+    mut ev_chunk_change: EventWriter<ChunkChangeEvent>,
 ) {
-    for SpawnBlockEvent {
-        chunk,
-        chunk_offset: (cx, cz),
-    } in ev_spawn_block.iter()
-    {
-        let block = world_grid
+    // The menus touch the resource every frame, so compare the value rather
+    // than relying on change detection
+    if *applied != Some(render_distance.0) {
+        *applied = Some(render_distance.0);
+        let load_radius = render_distance
             .0
-            .entry(chunk.clone())
-            .or_insert_with(|| propogate_block(chunk.0, chunk.1, Some(seed.0)));
-
-        for (x, col) in block.iter().enumerate() {
-            for (z, t) in col.iter().enumerate() {
-                let (x, z) = (x as f32, z as f32);
-                let (x, z) = (
-                    x + (FBLOCK_SIZE) * (*cx as f32),
-                    z + (FBLOCK_SIZE) * (*cz as f32),
-                );
-                // Crate the entities that should go here
-                entities_for_tile(
-                    &mut commands,
-                    &mut materials,
-                    t,
-                    chunk,
-                    (Some(x), None, Some(z)),
-                    &objects,
-                );
+            .clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE)
+            - 1;
+        streaming.set_radius(load_radius, STREAMING_HYSTERESIS);
+        // Staying in the same chunk lets the streaming policy catch up
+        if !streaming.is_empty() {
+            ev_chunk_change.send(ChunkChangeEvent {
+                oldchunk: *current_chunk,
+                newchunk: *current_chunk,
+            });
+        }
+    }
+}
+
+/// Keep the active profile in step with the chosen preset, including when
+/// its file is edited
+fn update_gen_profile(
+    mut ev_asset: EventReader<AssetEvent<GenProfile>>,
+    profiles: Res<Assets<GenProfile>>,
+    presets: Res<GenPresets>,
+    selected: Res<SelectedGenProfile>,
+    mut active: ResMut<ActiveGenProfile>,
+) {
+    if ev_asset.iter().count() > 0 || selected.is_changed() {
+        if let Some(profile) = presets
+            .0
+            .get(&selected.0)
+            .and_then(|handle| profiles.get(handle))
+        {
+            if *profile != active.0 {
+                active.0 = profile.clone();
             }
         }
     }
 }
 
-fn cull_far_entities(
-    mut ev_chunk_change: EventReader<ChunkChangeEvent>,
-    terrain: Query<(Entity, &Terrain)>,
-    mut loaded_chunks: ResMut<LoadedChunks>,
+/// Gather up the prefabs again whenever one loads or is edited
+fn update_prefabs(
+    mut ev_asset: EventReader<AssetEvent<Prefab>>,
+    assets: Res<Assets<Prefab>>,
+    prefabs: Res<Prefabs>,
+    mut active: ResMut<ActivePrefabs>,
+) {
+    if ev_asset.iter().count() > 0 {
+        // Sorted so that the same seed always picks the same prefabs
+        let mut names: Vec<&String> = prefabs.0.keys().collect();
+        names.sort();
+        let loaded: Vec<Prefab> = names
+            .into_iter()
+            .filter_map(|name| assets.get(&prefabs.0[name]).cloned())
+            .collect();
+        if loaded != *active.0 {
+            active.0 = Arc::new(loaded);
+        }
+    }
+}
+
+/// Throw away everything generated with an old profile or prefabs and rebuild
+/// the chunks around the player, other than chunks that have been edited
+fn regenerate_world(
     mut commands: Commands,
+    profile: Res<ActiveGenProfile>,
+    prefabs: Res<ActivePrefabs>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut generating_chunks: ResMut<GeneratingChunks>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut streaming: ResMut<StreamingPolicy>,
+    terrain: Query<Entity, With<Terrain>>,
+    current_chunk: Res<Chunk>,
+    mut ev_chunk_change: EventWriter<ChunkChangeEvent>,
+) {
+    if profile.is_changed() || prefabs.is_changed() {
+        let WorldGrid {
+            generated,
+            deltas,
+            edited,
+        } = &mut *world_grid;
+        generated.retain(|chunk, _| edited.contains(chunk));
+        // Damage is kept when prefabs load or change, but a new profile makes
+        // different terrain that the old damage doesn't belong to
+        if profile.is_changed() {
+            deltas.retain(|chunk, _| edited.contains(chunk));
+        }
+        generating_chunks.0.clear();
+        spawn_queue.0.clear();
+        for ent in terrain.iter() {
+            commands.entity(ent).despawn_recursive();
+        }
+        if streaming.reset() {
+            ev_chunk_change.send(ChunkChangeEvent {
+                oldchunk: *current_chunk,
+                newchunk: *current_chunk,
+            });
+        }
+    }
+}
+
+fn handle_spawn_block(
+    mut ev_spawn_block: EventReader<SpawnBlockEvent>,
+    world_grid: Res<WorldGrid>,
+    seed: Res<Seed>,
+    profile: Res<ActiveGenProfile>,
+    prefabs: Res<ActivePrefabs>,
+    mut generating_chunks: ResMut<GeneratingChunks>,
+    mut spawn_queue: ResMut<SpawnQueue>,
 ) {
-    if let Some(ChunkChangeEvent {
-        oldchunk: _,
-        newchunk,
-    }) = ev_chunk_change.iter().last()
-    {
-        for (en, Terrain(x, z)) in terrain.iter() {
-            if DVec2::new(*x as f64, *z as f64)
-                .distance(DVec2::new(newchunk.0 as f64, newchunk.1 as f64))
-                > (RENDER_DISTANCE as f64)
-            {
-                loaded_chunks.0.remove(&Chunk(*x, *z));
-                commands.entity(en).despawn_recursive();
+    let pool = AsyncComputeTaskPool::get();
+    for SpawnBlockEvent { chunk } in ev_spawn_block.iter() {
+        if world_grid.generated.contains_key(chunk) {
+            if !spawn_queue.0.iter().any(|(queued, _)| queued == chunk) {
+                spawn_queue.0.push_back((*chunk, 0));
             }
+        } else if !generating_chunks.0.contains_key(chunk) {
+            let (chunk, seed, profile) = (*chunk, seed.0, profile.0.clone());
+            let prefabs = prefabs.0.clone();
+            let task =
+                pool.spawn(async move { propogate_block(&chunk, Some(seed), &profile, &prefabs) });
+            generating_chunks.0.insert(chunk, task);
         }
     }
 }
 
-fn propogate_block(
-    chunk_x: usize,
-    chunk_z: usize,
-    seed: Option<u32>,
-) -> [[TileSettings; BLOCK_SIZE]; BLOCK_SIZE] {
-    let seed = seed.unwrap_or(0);
-    let perlin_elev = Perlin::new().set_seed(seed);
-    let perlin_trees = Perlin::new().set_seed(seed.wrapping_add(100));
-    let perlin_items = Perlin::new().set_seed(seed.wrapping_add(200));
+fn collect_generated_chunks(
+    mut generating_chunks: ResMut<GeneratingChunks>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    streaming: Res<StreamingPolicy>,
+) {
+    generating_chunks.0.retain(|chunk, task| {
+        match future::block_on(future::poll_once(task)) {
+            Some(block) => {
+                world_grid.generated.insert(*chunk, block);
+                // The player may have moved on while this was being generated
+                if streaming.is_loaded(chunk) {
+                    spawn_queue.0.push_back((*chunk, 0));
+                }
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+fn spawn_queued_tiles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    palette: Res<TilePalette>,
+    objects: Res<Objects>,
+    world_grid: Res<WorldGrid>,
+    current_chunk: Res<Chunk>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+) {
+    let mut budget = SPAWN_BUDGET;
+    while budget > 0 {
+        let (chunk, next) = match spawn_queue.0.front_mut() {
+            Some((chunk, next)) => (*chunk, next),
+            None => break,
+        };
+        let block = world_grid
+            .block(&chunk)
+            .expect("Queued chunks should already be generated");
+
+        // Work out the offset now rather than when the chunk was requested
+        // since the floating origin may have moved in the meantime
+        let offset = chunk.to_world(&current_chunk);
 
-    let (chunk_x, chunk_z) = (chunk_x as f64, chunk_z as f64);
+        if *next == 0 {
+            make_chunk_terrain(&mut commands, &mut meshes, &palette, &block, &chunk, offset);
+        }
 
-    let mut block = [[TileSettings { ..default() }; BLOCK_SIZE]; BLOCK_SIZE];
-    let mut has_cage = false;
-    let mut launcher_count = 2;
+        while budget > 0 && *next < BLOCK_SIZE * BLOCK_SIZE {
+            let (tx, tz) = (*next / BLOCK_SIZE, *next % BLOCK_SIZE);
+            let (x, z) = (tx as f32 + offset.x, tz as f32 + offset.z);
+            // Crate the entities that should go here
+            entities_for_tile(
+                &mut commands,
+                &block[tx][tz],
+                &chunk,
+                (tx, tz),
+                (Some(x), None, Some(z)),
+                &objects,
+            );
+            *next += 1;
+            budget -= 1;
+        }
 
-    // Visit blocks in a pseudo random order
-    let mut rng = rand::rngs::StdRng::seed_from_u64(
-        ((chunk_x as u64) * (2_u64.pow(32)) + (chunk_z as u64)).wrapping_add(seed as u64),
-    );
+        if *next >= BLOCK_SIZE * BLOCK_SIZE {
+            spawn_queue.0.pop_front();
+        }
+    }
+}
 
-    let mut rows: Vec<usize> = (0..BLOCK_SIZE).collect();
-    while !rows.is_empty() {
-        let x = rows.swap_remove(rng.gen::<usize>() % rows.len());
-        let mut cols: Vec<usize> = (0..BLOCK_SIZE).collect();
-        while !cols.is_empty() {
-            let z = cols.swap_remove(rng.gen::<usize>() % cols.len());
-            let perlin_x = (chunk_x * (BLOCK_SIZE as f64) + x as f64) / 10.; //(BLOCK_SIZE as f64);
-            let perlin_z = (chunk_z * (BLOCK_SIZE as f64) + z as f64) / 10.; //(BLOCK_SIZE as f64);
-            let elev = perlin_elev.get([perlin_x, perlin_z]);
-            let trees = perlin_trees.get([perlin_x, perlin_z]);
+fn record_terrain_change(
+    mut ev_terrain_change: EventReader<TerrainChangeEvent>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut on_tiles: Query<(&Terrain, &TileRef, &mut Transform)>,
+) {
+    for TerrainChangeEvent { chunk, tile } in ev_terrain_change.iter() {
+        let old_height = world_grid.tile(chunk, tile.x, tile.z).map(|t| t.height);
+        world_grid
+            .deltas
+            .entry(*chunk)
+            .or_default()
+            .entry((tile.x, tile.z))
+            .or_default()
+            .record(tile.part);
 
-            let items = perlin_items.get([perlin_x, perlin_z]);
+        if let TilePart::Block { .. } = tile.part {
+            dirty_chunks.0.insert(*chunk);
 
-            if elev > 0.125 {
-                let norm = (elev - 0.125) / (1.0 - 0.125);
-                block[x as usize][z as usize].kind = TileType::B;
-                block[x as usize][z as usize].height =
-                    ((norm * ((MAX_ELEV - 1) as f64)) as usize) + 1;
+            // Bring down anything that was standing on the tile with it
+            let new_height = world_grid.tile(chunk, tile.x, tile.z).map(|t| t.height);
+            if let (Some(old_height), Some(new_height)) = (old_height, new_height) {
+                let drop = old_height.saturating_sub(new_height) as f32;
+                for (_, _, mut transform) in on_tiles.iter_mut().filter(|(terrain, on, _)| {
+                    terrain.0 == *chunk && on.x == tile.x && on.z == tile.z
+                }) {
+                    transform.translation.y -= drop;
+                }
             }
+        }
+    }
+}
+
+fn refresh_edited_tiles(
+    mut commands: Commands,
+    mut ev_tile_edited: EventReader<TileEditedEvent>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    objects: Res<Objects>,
+    world_grid: Res<WorldGrid>,
+    streaming: Res<StreamingPolicy>,
+    current_chunk: Res<Chunk>,
+    on_tiles: Query<(Entity, &Terrain, &TileRef)>,
+) {
+    for TileEditedEvent { chunk, x, z } in ev_tile_edited.iter() {
+        let tile = match world_grid.tile(chunk, *x, *z) {
+            Some(tile) if streaming.is_loaded(chunk) => tile,
+            _ => continue,
+        };
+        dirty_chunks.0.insert(*chunk);
 
-            if 0 != launcher_count && items > 0. {
-                launcher_count -= 1;
-                block[x as usize][z as usize].item = Some(ItemType::Launcher);
-            } else if !has_cage && items > 0.9 {
-                has_cage = true;
-                block[x as usize][z as usize].item = Some(ItemType::Cage);
-            } else if trees > 0.7 {
-                block[x as usize][z as usize].copse = true;
+        for (ent, _, _) in on_tiles
+            .iter()
+            .filter(|(_, terrain, on)| terrain.0 == *chunk && on.x == *x && on.z == *z)
+        {
+            commands.entity(ent).despawn_recursive();
+        }
+        let offset = chunk.to_world(&current_chunk);
+        entities_for_tile(
+            &mut commands,
+            &tile,
+            chunk,
+            (*x, *z),
+            (Some(*x as f32 + offset.x), None, Some(*z as f32 + offset.z)),
+            &objects,
+        );
+    }
+}
+
+fn blast_terrain(
+    mut ev_explosion: EventReader<ExplosionEvent>,
+    mut ev_terrain_change: EventWriter<TerrainChangeEvent>,
+    world_grid: Res<WorldGrid>,
+    streaming: Res<StreamingPolicy>,
+    current_chunk: Res<Chunk>,
+) {
+    for ExplosionEvent { pos, radius } in ev_explosion.iter() {
+        let reach = radius.ceil() as isize;
+        let (px, pz) = (pos.x.round() as isize, pos.z.round() as isize);
+        for (x, z) in iproduct!(px - reach..=px + reach, pz - reach..=pz + reach) {
+            let (chunk, tx, tz) =
+                Chunk::from_world(&current_chunk, Vec3::new(x as f32, 0., z as f32));
+            if !streaming.is_loaded(&chunk) {
+                continue;
             }
+            if let Some(tile) = world_grid.tile(&chunk, tx, tz) {
+                let height = tile.height;
+                let distance = Vec3::new(x as f32, height as f32, z as f32).distance(*pos);
+                if height == 0 || distance >= *radius {
+                    continue;
+                }
+                // Dig deepest in the middle to leave a crater
+                let lowered = (BLAST_DEPTH * (1. - distance / radius)).ceil() as usize;
+                ev_terrain_change.send(TerrainChangeEvent {
+                    chunk,
+                    tile: TileRef {
+                        x: tx,
+                        z: tz,
+                        part: TilePart::Block { lowered },
+                    },
+                });
+            }
+        }
+    }
+}
+
+fn rebuild_dirty_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    palette: Res<TilePalette>,
+    world_grid: Res<WorldGrid>,
+    current_chunk: Res<Chunk>,
+    terrain: Query<(Entity, &Terrain), With<ChunkTerrain>>,
+) {
+    for chunk in std::mem::take(&mut dirty_chunks.0) {
+        let mut was_spawned = false;
+        for (ent, _) in terrain
+            .iter()
+            .filter(|(_, Terrain(terrain_chunk))| *terrain_chunk == chunk)
+        {
+            commands.entity(ent).despawn_recursive();
+            was_spawned = true;
+        }
+
+        // Chunks that haven't been spawned yet will pick up the change when
+        // they are
+        if let (true, Some(block)) = (was_spawned, world_grid.block(&chunk)) {
+            make_chunk_terrain(
+                &mut commands,
+                &mut meshes,
+                &palette,
+                &block,
+                &chunk,
+                chunk.to_world(&current_chunk),
+            );
         }
     }
-    block
 }
@@ -0,0 +1,358 @@
+//! Named actions that the player can take, and the keys and gamepad buttons
+//! that are bound to them
+
+use crate::gamepad::{self, GamepadInput};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How many keys can be bound to each action
+pub const KEYS_PER_ACTION: usize = 2;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Interact,
+    SwitchLeader,
+    Pause,
+    /// Held while clicking to tell the flock to stay where it is
+    HoldOrder,
+    /// Held while clicking to call the flock back to the leader
+    RegroupOrder,
+    /// Held while clicking to send the flock away from the leader
+    ScatterOrder,
+    /// Only debug builds used to have jumping, which was called flying
+    #[serde(alias = "Fly")]
+    Jump,
+    ClearFlock,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Interact,
+        Action::SwitchLeader,
+        Action::Pause,
+        Action::HoldOrder,
+        Action::RegroupOrder,
+        Action::ScatterOrder,
+        Action::Jump,
+        Action::ClearFlock,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Interact => "Open Cage",
+            Action::SwitchLeader => "Switch Leader",
+            Action::Pause => "Pause",
+            Action::HoldOrder => "Click to Hold",
+            Action::RegroupOrder => "Click to Regroup",
+            Action::ScatterOrder => "Click to Scatter",
+            Action::Jump => "Jump",
+            Action::ClearFlock => "Remove Flock",
+        }
+    }
+
+    /// Whether the action only does anything in debug builds
+    pub fn debug_only(self) -> bool {
+        matches!(self, Action::ClearFlock)
+    }
+
+    /// The gamepad button that also does this, which can't be rebound
+    pub fn button(self) -> Option<GamepadButtonType> {
+        match self {
+            Action::Interact => Some(gamepad::INTERACT),
+            Action::SwitchLeader => Some(gamepad::SWITCH_LEADER),
+            Action::Pause => Some(gamepad::PAUSE),
+            Action::Jump => Some(gamepad::JUMP),
+            _ => None,
+        }
+    }
+
+    fn default_keys(self) -> [Option<KeyCode>; KEYS_PER_ACTION] {
+        match self {
+            Action::MoveUp => [Some(KeyCode::W), Some(KeyCode::Up)],
+            Action::MoveDown => [Some(KeyCode::S), Some(KeyCode::Down)],
+            Action::MoveLeft => [Some(KeyCode::A), Some(KeyCode::Left)],
+            Action::MoveRight => [Some(KeyCode::D), Some(KeyCode::Right)],
+            Action::Interact => [Some(KeyCode::E), None],
+            Action::SwitchLeader => [Some(KeyCode::Q), None],
+            Action::Pause => [Some(KeyCode::Escape), None],
+            Action::HoldOrder => [Some(KeyCode::LShift), Some(KeyCode::RShift)],
+            Action::RegroupOrder => [Some(KeyCode::LControl), Some(KeyCode::RControl)],
+            Action::ScatterOrder => [Some(KeyCode::LAlt), Some(KeyCode::RAlt)],
+            Action::Jump => [Some(KeyCode::Space), None],
+            Action::ClearFlock => [Some(KeyCode::Back), None],
+        }
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("“{:?}”", key)
+}
+
+/// The keys bound to each action, with no key bound to more than one action
+/// and every action having at least one key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct Bindings(HashMap<Action, [Option<KeyCode>; KEYS_PER_ACTION]>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings(
+            Action::ALL
+                .iter()
+                .map(|&action| (action, action.default_keys()))
+                .collect(),
+        )
+    }
+}
+
+impl Bindings {
+    pub fn key(&self, action: Action, slot: usize) -> Option<KeyCode> {
+        self.0.get(&action).and_then(|keys| keys[slot])
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = KeyCode> + '_ {
+        self.0.get(&action).into_iter().flatten().flatten().copied()
+    }
+
+    /// Which action and slot a key is bound to, if any
+    pub fn bound_to(&self, key: KeyCode) -> Option<(Action, usize)> {
+        Action::ALL.iter().find_map(|&action| {
+            (0..KEYS_PER_ACTION)
+                .find(|&slot| self.key(action, slot) == Some(key))
+                .map(|slot| (action, slot))
+        })
+    }
+
+    /// The keys for an action written out for the player, like “E” or “Up”
+    pub fn describe(&self, action: Action) -> String {
+        self.keys(action)
+            .map(key_name)
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+
+    fn slots_mut(&mut self, action: Action) -> &mut [Option<KeyCode>; KEYS_PER_ACTION] {
+        self.0.entry(action).or_insert([None; KEYS_PER_ACTION])
+    }
+
+    /// Keep an action's keys at the front of its slots
+    fn tidy(&mut self, action: Action) {
+        let keys: Vec<_> = self.keys(action).collect();
+        let slots = self.slots_mut(action);
+        *slots = [None; KEYS_PER_ACTION];
+        for (slot, key) in slots.iter_mut().zip(keys) {
+            *slot = Some(key);
+        }
+    }
+
+    /// Bind `key` to one of an action's slots.
+    ///
+    /// If another action already has the key, the two swap keys, and that
+    /// action is returned so that the player can be told. The swap is refused
+    /// if it would leave the other action with no keys at all.
+    pub fn rebind(
+        &mut self,
+        action: Action,
+        slot: usize,
+        key: KeyCode,
+    ) -> Result<Option<Action>, String> {
+        let old = self.key(action, slot);
+        let taken = self.bound_to(key);
+        match taken {
+            Some((other, other_slot)) if other == action => {
+                // Moving a key between an action's own slots just swaps them
+                self.slots_mut(action).swap(slot, other_slot);
+                self.tidy(action);
+                return Ok(None);
+            }
+            Some((other, _)) if old.is_none() && self.keys(other).count() == 1 => {
+                return Err(format!(
+                    "{} is the only key for {}",
+                    key_name(key),
+                    other.name()
+                ));
+            }
+            Some((other, other_slot)) => {
+                self.slots_mut(other)[other_slot] = old;
+                self.tidy(other);
+            }
+            None => {}
+        }
+        self.slots_mut(action)[slot] = Some(key);
+        self.tidy(action);
+        Ok(taken.map(|(other, _)| other))
+    }
+
+    /// Unbind one of an action's keys, as long as it has another
+    pub fn clear(&mut self, action: Action, slot: usize) -> Result<(), String> {
+        if self.key(action, slot).is_some() && self.keys(action).count() == 1 {
+            return Err(format!("{} needs at least one key", action.name()));
+        }
+        self.slots_mut(action)[slot] = None;
+        self.tidy(action);
+        Ok(())
+    }
+
+    /// Use saved bindings in place of the defaults, unless they give a key to
+    /// more than one action or leave an action without any
+    pub fn load(&mut self, saved: &Bindings) -> Result<(), String> {
+        let mut loaded = Bindings::default();
+        for (&action, &keys) in saved.0.iter() {
+            *loaded.slots_mut(action) = keys;
+            loaded.tidy(action);
+        }
+        let mut seen = HashSet::new();
+        if !Action::ALL
+            .iter()
+            .flat_map(|&action| loaded.keys(action))
+            .all(|key| seen.insert(key))
+        {
+            return Err("The same key is saved for more than one action".to_string());
+        }
+        if let Some(action) = Action::ALL
+            .iter()
+            .find(|&&action| loaded.keys(action).next().is_none())
+        {
+            return Err(format!("{} is saved without any keys", action.name()));
+        }
+        *self = loaded;
+        Ok(())
+    }
+}
+
+/// Whether actions are being done, from the keyboard or any gamepad
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    gamepads: GamepadInput<'w, 's>,
+    bindings: Res<'w, Bindings>,
+}
+
+impl<'w, 's> ActionInput<'w, 's> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings
+            .keys(action)
+            .any(|key| self.keyboard_input.pressed(key))
+            || action
+                .button()
+                .map_or(false, |button| self.gamepads.pressed(button))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.bindings
+            .keys(action)
+            .any(|key| self.keyboard_input.just_pressed(key))
+            || action
+                .button()
+                .map_or(false, |button| self.gamepads.just_pressed(button))
+    }
+
+    /// Which way the player is being moved, with up as positive y. Keys move
+    /// at full speed, while the left stick moves as far as it is tilted.
+    pub fn movement(&self) -> Vec2 {
+        let tilt = self.gamepads.left_stick();
+        let x = if self.pressed(Action::MoveLeft) {
+            -1.
+        } else if self.pressed(Action::MoveRight) {
+            1.
+        } else {
+            tilt.x
+        };
+        let y = if self.pressed(Action::MoveDown) {
+            -1.
+        } else if self.pressed(Action::MoveUp) {
+            1.
+        } else {
+            tilt.y
+        };
+        Vec2::new(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let mut bindings = Bindings::default();
+        assert_eq!(bindings.load(&Bindings::default()), Ok(()));
+        assert_eq!(bindings.bound_to(KeyCode::Up), Some((Action::MoveUp, 1)));
+        assert_eq!(bindings.describe(Action::MoveLeft), "“A” or “Left”");
+    }
+
+    #[test]
+    fn taken_keys_are_swapped() {
+        let mut bindings = Bindings::default();
+        assert_eq!(
+            bindings.rebind(Action::Interact, 0, KeyCode::Q),
+            Ok(Some(Action::SwitchLeader))
+        );
+        assert_eq!(bindings.key(Action::Interact, 0), Some(KeyCode::Q));
+        assert_eq!(bindings.key(Action::SwitchLeader, 0), Some(KeyCode::E));
+
+        // Swapping within an action just changes the order
+        assert_eq!(bindings.rebind(Action::MoveUp, 0, KeyCode::Up), Ok(None));
+        assert_eq!(bindings.describe(Action::MoveUp), "“Up” or “W”");
+    }
+
+    #[test]
+    fn actions_always_keep_a_key() {
+        let mut bindings = Bindings::default();
+        // Taking Pause's only key would need a key to give it back
+        assert!(bindings
+            .rebind(Action::Interact, 1, KeyCode::Escape)
+            .is_err());
+        assert_eq!(bindings.key(Action::Pause, 0), Some(KeyCode::Escape));
+        assert!(bindings.clear(Action::Pause, 0).is_err());
+
+        // Spare keys can be cleared, and the rest move to the front
+        assert_eq!(bindings.clear(Action::MoveDown, 0), Ok(()));
+        assert_eq!(bindings.key(Action::MoveDown, 0), Some(KeyCode::Down));
+        assert_eq!(bindings.key(Action::MoveDown, 1), None);
+        // Taking another action's spare key is fine
+        assert_eq!(
+            bindings.rebind(Action::Interact, 1, KeyCode::Left),
+            Ok(Some(Action::MoveLeft))
+        );
+        assert_eq!(bindings.describe(Action::MoveLeft), "“A”");
+    }
+
+    #[test]
+    fn conflicting_saves_are_ignored() {
+        let mut saved = Bindings::default();
+        saved.rebind(Action::Pause, 1, KeyCode::P).unwrap();
+        let mut bindings = Bindings::default();
+        assert_eq!(bindings.load(&saved), Ok(()));
+        assert_eq!(bindings, saved);
+
+        // Edited by hand to give “E” to two actions
+        saved
+            .0
+            .insert(Action::SwitchLeader, [Some(KeyCode::E), None]);
+        assert!(bindings.load(&saved).is_err());
+        assert_eq!(bindings.key(Action::SwitchLeader, 0), Some(KeyCode::Q));
+
+        // Actions missing from older saves keep their defaults
+        let mut partial = Bindings(HashMap::new());
+        *partial.slots_mut(Action::Interact) = [Some(KeyCode::F), None];
+        let mut bindings = Bindings::default();
+        assert_eq!(bindings.load(&partial), Ok(()));
+        assert_eq!(bindings.describe(Action::Interact), "“F”");
+        assert_eq!(bindings.describe(Action::Pause), "“Escape”");
+    }
+}
@@ -1,24 +1,42 @@
+use crate::controls::{Action, ActionInput};
+use crate::flocking::FlockWeights;
 use crate::game_plugin::NewGameEvent;
+use crate::health_plugin::{DamageCause, DamageEvent};
 use crate::menu_plugin::Menu;
 use crate::player_manager_plugin::{Minion, Player};
+use crate::streaming::StreamingPolicy;
 use bevy::app::Plugin;
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, EntityCountDiagnosticsPlugin, LogDiagnosticsPlugin,
+};
 use bevy::prelude::*;
-use bevy_inspector_egui::WorldInspectorPlugin;
+use bevy_inspector_egui::{InspectorPlugin, WorldInspectorPlugin};
 use bevy_inspector_egui_rapier::InspectableRapierPlugin;
 use bevy_mod_picking::DebugCursorPickingPlugin;
 use bevy_rapier3d::prelude::RapierDebugRenderPlugin;
 
 pub struct DebugPlugin;
 
+const CHUNK_LOADS: DiagnosticId =
+    DiagnosticId::from_u128(0x5b0e_93c1_7a24_4f6d_8e1b_c2d9_4a07_3f18);
+const CHUNK_UNLOADS: DiagnosticId =
+    DiagnosticId::from_u128(0x1d6a_4e82_b3f5_49c0_a7d1_65e9_0b2c_8f34);
+
 impl Plugin for DebugPlugin {
     #[cfg(build = "debug")]
     fn build(&self, app: &mut App) {
         app.add_plugin(WorldInspectorPlugin::new())
+            .add_plugin(InspectorPlugin::<FlockWeights>::new())
             .add_plugin(InspectableRapierPlugin)
             .add_plugin(RapierDebugRenderPlugin::default())
+            .add_plugin(EntityCountDiagnosticsPlugin)
+            .add_plugin(LogDiagnosticsPlugin::default())
+            .add_startup_system(setup_streaming_diagnostics)
+            .add_system(streaming_diagnostics)
             .add_system(controls)
             .add_startup_system(new_game_debug)
-            .add_plugin(DebugCursorPickingPlugin);
+            .add_plugin(DebugCursorPickingPlugin)
+            .add_plugin(crate::editor_plugin::EditorPlugin);
     }
 
     #[cfg(not(build = "debug"))]
@@ -31,13 +49,36 @@ fn new_game_debug(mut ev_new_game: EventWriter<NewGameEvent>, mut menu: ResMut<M
 }
 
 fn controls(
-    keyboard_input: Res<Input<KeyCode>>,
+    controls: ActionInput,
     controlables: Query<Entity, Or<(&Player, &Minion)>>,
-    mut commands: Commands,
+    mut ev_damage: EventWriter<DamageEvent>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Back) {
+    if controls.just_pressed(Action::ClearFlock) {
         for ent in controlables.iter() {
-            commands.entity(ent).despawn_recursive();
+            ev_damage.send(DamageEvent {
+                target: ent,
+                amount: 0,
+                cause: DamageCause::Removed,
+            });
         }
     }
 }
+
+fn setup_streaming_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(CHUNK_LOADS, "chunk_loads_per_minute", 1));
+    diagnostics.add(Diagnostic::new(
+        CHUNK_UNLOADS,
+        "chunk_unloads_per_minute",
+        1,
+    ));
+}
+
+fn streaming_diagnostics(
+    mut diagnostics: ResMut<Diagnostics>,
+    mut streaming: ResMut<StreamingPolicy>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    diagnostics.add_measurement(CHUNK_LOADS, || streaming.loads_per_minute(now) as f64);
+    diagnostics.add_measurement(CHUNK_UNLOADS, || streaming.unloads_per_minute(now) as f64);
+}
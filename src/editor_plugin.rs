@@ -0,0 +1,230 @@
+//! An in-game editor for painting chunks by hand and exporting them as
+//! prefabs, only built into debug builds.
+//!
+//! Press F2 to open it, then click on the terrain to edit the tile under the
+//! cursor.
+
+use crate::asset_plugin::Prefabs;
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::{TileEditedEvent, WorldGrid, MAX_ELEV};
+use crate::item_plugin::ItemType;
+use crate::share::{ChunkTerrain, TileSettings, TileType};
+use crate::world_gen::Prefab;
+use bevy::app::Plugin;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_egui::EguiContext;
+use bevy_mod_picking::PickingCamera;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorState>()
+            .add_system(toggle_editor)
+            .add_system(editor_window)
+            .add_system(edit_clicked_tile.before("click_orders"));
+    }
+}
+
+/// What a click on the terrain does
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum EditorTool {
+    Paint,
+    Raise,
+    Lower,
+    Copse,
+    Item,
+}
+
+struct EditorState {
+    enabled: bool,
+    tool: EditorTool,
+    kind: TileType,
+    item: ItemType,
+    /// The chunk that was last edited, which is the one that gets exported
+    chunk: Option<Chunk>,
+    name: String,
+    /// Whether the export is a fixed level for its chunk rather than a prefab
+    /// that can turn up anywhere
+    fixed: bool,
+    status: String,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        EditorState {
+            enabled: false,
+            tool: EditorTool::Raise,
+            kind: TileType::Base,
+            item: ItemType::Cage,
+            chunk: None,
+            name: "custom".to_string(),
+            fixed: false,
+            status: String::new(),
+        }
+    }
+}
+
+fn toggle_editor(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<EditorState>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        state.enabled = !state.enabled;
+    }
+}
+
+fn editor_window(
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<EditorState>,
+    mut prefabs: ResMut<Prefabs>,
+    asset_server: Res<AssetServer>,
+    world_grid: Res<WorldGrid>,
+    current_chunk: Res<Chunk>,
+) {
+    if !state.enabled {
+        return;
+    }
+    let state = &mut *state;
+    let chunk = state.chunk.unwrap_or(*current_chunk);
+    egui::Window::new("Level Editor").show(egui_context.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for (tool, name) in [
+                (EditorTool::Paint, "Paint"),
+                (EditorTool::Raise, "Raise"),
+                (EditorTool::Lower, "Lower"),
+                (EditorTool::Copse, "Trees"),
+                (EditorTool::Item, "Item"),
+            ] {
+                ui.selectable_value(&mut state.tool, tool, name);
+            }
+        });
+        egui::ComboBox::from_label("Tile")
+            .selected_text(format!("{:?}", state.kind))
+            .show_ui(ui, |ui| {
+                for kind in TileType::ALL {
+                    ui.selectable_value(&mut state.kind, kind, format!("{:?}", kind));
+                }
+            });
+        egui::ComboBox::from_label("Item")
+            .selected_text(format!("{:?}", state.item))
+            .show_ui(ui, |ui| {
+                for item in [ItemType::Cage, ItemType::Launcher, ItemType::Bomb] {
+                    ui.selectable_value(&mut state.item, item, format!("{:?}", item));
+                }
+            });
+
+        ui.separator();
+        ui.label(format!("Chunk ({}, {})", chunk.0, chunk.1));
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut state.name);
+        });
+        ui.checkbox(&mut state.fixed, "Fixed level for this chunk");
+        if ui.button("Export").clicked() {
+            state.status = match export_chunk(
+                &state.name,
+                &chunk,
+                state.fixed,
+                &world_grid,
+                &asset_server,
+                &mut prefabs,
+            ) {
+                Ok(path) => format!("Saved {}, the world will be rebuilt", path),
+                Err(e) => e,
+            };
+        }
+        if !state.status.is_empty() {
+            ui.label(state.status.as_str());
+        }
+    });
+}
+
+/// Save a chunk as a prefab and load it so that it's used straight away
+fn export_chunk(
+    name: &str,
+    chunk: &Chunk,
+    fixed: bool,
+    world_grid: &WorldGrid,
+    asset_server: &AssetServer,
+    prefabs: &mut Prefabs,
+) -> Result<String, String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("Names can only use letters, numbers and underscores".to_string());
+    }
+    let block = world_grid
+        .block(chunk)
+        .ok_or("The chunk hasn't been generated")?;
+    let prefab = Prefab::from_block(name, &block, fixed.then_some(*chunk))?;
+    let json = serde_json::to_string_pretty(&prefab).map_err(|e| e.to_string())?;
+
+    let path = format!("prefabs/{}.prefab.json", name);
+    std::fs::write(format!("assets/{}", path), json).map_err(|e| format!("{}: {}", path, e))?;
+    match prefabs.0.contains_key(name) {
+        true => asset_server.reload_asset(path.as_str()),
+        false => {
+            prefabs
+                .0
+                .insert(name.to_string(), asset_server.load(path.as_str()));
+        }
+    }
+    Ok(path)
+}
+
+fn edit_clicked_tile(
+    mut mouse_input: ResMut<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<EditorState>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut ev_tile_edited: EventWriter<TileEditedEvent>,
+    current_chunk: Res<Chunk>,
+    cameras: Query<&PickingCamera>,
+    terrain: Query<(), With<ChunkTerrain>>,
+) {
+    if !state.enabled
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || egui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    let hit = cameras
+        .iter()
+        .filter_map(|camera| camera.intersect_top())
+        .find(|(ent, _)| terrain.get(*ent).is_ok());
+    if let Some((_, intersection)) = hit {
+        // The click is used up so that the leader doesn't walk there too
+        mouse_input.reset(MouseButton::Left);
+        // Step just inside the surface so that clicking the side of a raised
+        // tile edits that tile rather than the one in front of it
+        let pos = intersection.position() - intersection.normal() * 0.1;
+        let (chunk, x, z) = Chunk::from_world(&current_chunk, pos);
+        let (tool, kind, item) = (state.tool, state.kind, state.item);
+        if world_grid.edit_tile(&chunk, x, z, |tile| apply_tool(tool, kind, item, tile)) {
+            state.chunk = Some(chunk);
+            ev_tile_edited.send(TileEditedEvent { chunk, x, z });
+        }
+    }
+}
+
+fn apply_tool(tool: EditorTool, kind: TileType, item: ItemType, tile: &mut TileSettings) {
+    match tool {
+        EditorTool::Paint => tile.kind = kind,
+        EditorTool::Raise => {
+            if tile.height == 0 {
+                tile.kind = tile.biome.raised();
+            }
+            tile.height = (tile.height + 1).min(MAX_ELEV);
+        }
+        EditorTool::Lower => {
+            tile.height = tile.height.saturating_sub(1);
+            if tile.height == 0 {
+                tile.kind = tile.biome.ground();
+            }
+        }
+        EditorTool::Copse => tile.copse = !tile.copse,
+        EditorTool::Item => {
+            tile.item = match tile.item {
+                Some(current) if current == item => None,
+                _ => Some(item),
+            }
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Boids-style steering for the flock
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use itertools::iproduct;
+use std::collections::HashMap;
+
+/// How strongly each part of flocking steers a minion, which can be tuned
+/// from the inspector in debug builds
+#[derive(Inspectable, Clone, Debug)]
+pub struct FlockWeights {
+    /// Keep out of the way of sheep that are too close
+    pub separation: f32,
+    /// Head the same way as the sheep nearby
+    pub alignment: f32,
+    /// Move towards the middle of the sheep nearby
+    pub cohesion: f32,
+    /// Head for the leader once it is too far away
+    pub leader: f32,
+    /// How far away other sheep count as nearby
+    pub neighbour_radius: f32,
+    /// How close other sheep can get before they are too close
+    pub separation_radius: f32,
+    /// How far from the leader sheep are happy to wander
+    pub leader_radius: f32,
+    /// How far from the leader sheep run when told to scatter
+    pub scatter_radius: f32,
+    /// The strongest force that flocking can put on a sheep
+    pub max_force: f32,
+}
+
+impl Default for FlockWeights {
+    fn default() -> Self {
+        FlockWeights {
+            separation: 3.,
+            alignment: 1.,
+            cohesion: 1.,
+            leader: 2.5,
+            neighbour_radius: 4.,
+            separation_radius: 1.5,
+            leader_radius: 3.,
+            scatter_radius: 10.,
+            max_force: 4.,
+        }
+    }
+}
+
+/// What the flock has been told to do by the player
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FlockOrder {
+    /// Keep up with the leader
+    #[default]
+    Follow,
+    /// Stay where they are, whatever the leader does
+    Hold,
+    /// Crowd in close around the leader
+    Regroup,
+    /// Run away from the leader
+    Scatter,
+}
+
+impl FlockOrder {
+    /// Give an order, where giving the same order again calls it off
+    pub fn toggle(&mut self, order: FlockOrder) {
+        *self = match *self == order {
+            true => FlockOrder::Follow,
+            false => order,
+        };
+    }
+}
+
+/// A sheep's position and velocity as far as flocking is concerned
+#[derive(Copy, Clone, Debug)]
+pub struct Boid {
+    pub pos: Vec3,
+    pub vel: Vec3,
+}
+
+/// Boids bucketed into square cells so that finding the ones nearby doesn't
+/// mean checking every pair
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(boids: &[Boid], cell_size: f32) -> Self {
+        let mut grid = SpatialGrid {
+            cell_size: cell_size.max(0.1),
+            cells: HashMap::new(),
+        };
+        for (i, boid) in boids.iter().enumerate() {
+            let cell = grid.cell(boid.pos);
+            grid.cells.entry(cell).or_default().push(i);
+        }
+        grid
+    }
+
+    fn cell(&self, pos: Vec3) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// The indices of the boids within `radius` of `pos` across the ground
+    pub fn neighbours<'a>(
+        &'a self,
+        boids: &'a [Boid],
+        pos: Vec3,
+        radius: f32,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let (cx, cz) = self.cell(pos);
+        iproduct!(cx - reach..=cx + reach, cz - reach..=cz + reach)
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |&i| flat(boids[i].pos - pos).length_squared() <= radius * radius)
+    }
+}
+
+/// Flocking only steers across the ground
+fn flat(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, 0., v.z)
+}
+
+/// The flocking force on the boid at `index`, which heads for or away from
+/// `leader` if there is one depending on the order, by way of `waypoint` if it
+/// has a path to follow
+pub fn steer(
+    index: usize,
+    boids: &[Boid],
+    grid: &SpatialGrid,
+    leader: Option<Vec3>,
+    waypoint: Option<Vec3>,
+    order: FlockOrder,
+    weights: &FlockWeights,
+) -> Vec3 {
+    let me = &boids[index];
+    let (mut separation, mut heading, mut centre, mut count) =
+        (Vec3::ZERO, Vec3::ZERO, Vec3::ZERO, 0);
+    for other in grid
+        .neighbours(boids, me.pos, weights.neighbour_radius)
+        .filter(|&other| other != index)
+    {
+        let offset = flat(me.pos - boids[other].pos);
+        let distance = offset.length();
+        if distance < weights.separation_radius {
+            // Sheep on top of each other still need pushing apart, so split
+            // them by which came first
+            let fallback = if index < other { Vec3::X } else { -Vec3::X };
+            let away = offset.try_normalize().unwrap_or(fallback);
+            separation += away * (1. - distance / weights.separation_radius);
+        }
+        heading += flat(boids[other].vel);
+        centre += flat(boids[other].pos);
+        count += 1;
+    }
+
+    let mut force = separation * weights.separation;
+    if count > 0 {
+        let count = count as f32;
+        force += (heading / count - flat(me.vel)) * weights.alignment;
+        force += (centre / count - flat(me.pos)).normalize_or_zero() * weights.cohesion;
+    }
+    if let Some(leader) = leader {
+        let to_leader = flat(leader - me.pos);
+        let target = waypoint.map_or(to_leader, |waypoint| flat(waypoint - me.pos));
+        let distance = to_leader.length();
+        match order {
+            FlockOrder::Follow if distance > weights.leader_radius => {
+                force += target.normalize_or_zero() * weights.leader;
+            }
+            FlockOrder::Regroup if distance > weights.separation_radius => {
+                force += target.normalize_or_zero() * weights.leader;
+            }
+            FlockOrder::Scatter if distance < weights.scatter_radius => {
+                force -= to_leader.normalize_or_zero() * weights.leader;
+            }
+            _ => {}
+        }
+    }
+    force.clamp_length_max(weights.max_force)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn still(positions: &[(f32, f32)]) -> Vec<Boid> {
+        positions
+            .iter()
+            .map(|&(x, z)| Boid {
+                pos: Vec3::new(x, 1., z),
+                vel: Vec3::ZERO,
+            })
+            .collect()
+    }
+
+    fn only(weights: FlockWeights, f: impl FnOnce(&mut FlockWeights)) -> FlockWeights {
+        let mut weights = FlockWeights {
+            separation: 0.,
+            alignment: 0.,
+            cohesion: 0.,
+            leader: 0.,
+            max_force: f32::MAX,
+            ..weights
+        };
+        f(&mut weights);
+        weights
+    }
+
+    #[test]
+    fn grid_finds_the_same_neighbours_as_checking_every_pair() {
+        let boids = still(&[
+            (0., 0.),
+            (1.5, 0.5),
+            (-3.9, 0.),
+            (4.1, 0.),
+            (-2., -2.),
+            (10., 10.),
+            (0., -3.99),
+        ]);
+        for cell_size in [0.5, 1., 4., 20.] {
+            let grid = SpatialGrid::new(&boids, cell_size);
+            let mut found: Vec<usize> = grid.neighbours(&boids, Vec3::ZERO, 4.).collect();
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..boids.len())
+                .filter(|&i| flat(boids[i].pos).length() <= 4.)
+                .collect();
+            assert_eq!(found, expected, "cell size {}", cell_size);
+        }
+    }
+
+    #[test]
+    fn sheep_that_are_too_close_push_apart() {
+        let boids = still(&[(0., 0.), (0.5, 0.), (0.5, 0.)]);
+        let weights = only(FlockWeights::default(), |w| w.separation = 1.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        assert!(steer(0, &boids, &grid, None, None, FlockOrder::Follow, &weights).x < 0.);
+        // Sheep in exactly the same place are still split up
+        let (a, b) = (
+            steer(1, &boids, &grid, None, None, FlockOrder::Follow, &weights),
+            steer(2, &boids, &grid, None, None, FlockOrder::Follow, &weights),
+        );
+        assert!(a.x != b.x);
+    }
+
+    #[test]
+    fn sheep_match_the_heading_of_the_flock() {
+        let mut boids = still(&[(0., 0.), (1., 0.), (-1., 0.)]);
+        boids[1].vel = Vec3::new(0., 0., 2.);
+        boids[2].vel = Vec3::new(0., 0., 2.);
+        let weights = only(FlockWeights::default(), |w| w.alignment = 1.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        let force = steer(0, &boids, &grid, None, None, FlockOrder::Follow, &weights);
+        assert_eq!(force, Vec3::new(0., 0., 2.));
+    }
+
+    #[test]
+    fn sheep_drift_to_the_middle_of_the_flock() {
+        let boids = still(&[(0., 0.), (2., 1.), (2., -1.)]);
+        let weights = only(FlockWeights::default(), |w| w.cohesion = 1.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        let force = steer(0, &boids, &grid, None, None, FlockOrder::Follow, &weights);
+        assert!((force - Vec3::X).length() < 1e-6);
+    }
+
+    #[test]
+    fn sheep_only_chase_a_far_away_leader() {
+        let boids = still(&[(0., 0.)]);
+        let weights = only(FlockWeights::default(), |w| w.leader = 2.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        let near = Vec3::new(0., 5., 2.);
+        assert_eq!(
+            steer(
+                0,
+                &boids,
+                &grid,
+                Some(near),
+                None,
+                FlockOrder::Follow,
+                &weights
+            ),
+            Vec3::ZERO
+        );
+        let far = Vec3::new(0., 5., -10.);
+        assert_eq!(
+            steer(
+                0,
+                &boids,
+                &grid,
+                Some(far),
+                None,
+                FlockOrder::Follow,
+                &weights
+            ),
+            Vec3::new(0., 0., -2.)
+        );
+    }
+
+    #[test]
+    fn sheep_follow_their_path_to_the_leader() {
+        let boids = still(&[(0., 0.)]);
+        let weights = only(FlockWeights::default(), |w| w.leader = 2.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        let (leader, waypoint) = (Vec3::new(10., 1., 0.), Vec3::new(0., 0., 1.));
+        assert_eq!(
+            steer(
+                0,
+                &boids,
+                &grid,
+                Some(leader),
+                Some(waypoint),
+                FlockOrder::Follow,
+                &weights
+            ),
+            Vec3::new(0., 0., 2.)
+        );
+        // The path doesn't matter once the leader is close enough
+        let leader = Vec3::new(1., 1., 0.);
+        assert_eq!(
+            steer(
+                0,
+                &boids,
+                &grid,
+                Some(leader),
+                Some(waypoint),
+                FlockOrder::Follow,
+                &weights
+            ),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn orders_change_how_sheep_treat_the_leader() {
+        let boids = still(&[(0., 0.)]);
+        let weights = only(FlockWeights::default(), |w| w.leader = 2.);
+        let grid = SpatialGrid::new(&boids, 4.);
+        let order =
+            |leader: Vec3, order| steer(0, &boids, &grid, Some(leader), None, order, &weights);
+
+        // Close enough to follow, but not close enough when regrouping
+        let near = Vec3::new(2., 1., 0.);
+        assert_eq!(order(near, FlockOrder::Follow), Vec3::ZERO);
+        assert_eq!(order(near, FlockOrder::Regroup), Vec3::new(2., 0., 0.));
+        assert_eq!(order(near, FlockOrder::Scatter), Vec3::new(-2., 0., 0.));
+
+        // Sheep stay where they are when holding, and once they have run far
+        // enough when scattering
+        let far = Vec3::new(0., 1., 20.);
+        assert_eq!(order(far, FlockOrder::Hold), Vec3::ZERO);
+        assert_eq!(order(far, FlockOrder::Scatter), Vec3::ZERO);
+        assert_eq!(order(far, FlockOrder::Follow), Vec3::new(0., 0., 2.));
+    }
+
+    #[test]
+    fn forces_are_capped() {
+        let boids = still(&[(0., 0.), (0.1, 0.), (0., 0.1), (0.1, 0.1)]);
+        let weights = FlockWeights::default();
+        let grid = SpatialGrid::new(&boids, 4.);
+        let force = steer(
+            0,
+            &boids,
+            &grid,
+            Some(Vec3::new(-50., 0., -50.)),
+            None,
+            FlockOrder::Follow,
+            &weights,
+        );
+        assert!(force.length() <= weights.max_force + 1e-4);
+    }
+
+    #[test]
+    fn giving_an_order_again_calls_it_off() {
+        let mut order = FlockOrder::default();
+        order.toggle(FlockOrder::Hold);
+        assert_eq!(order, FlockOrder::Hold);
+        order.toggle(FlockOrder::Scatter);
+        assert_eq!(order, FlockOrder::Scatter);
+        order.toggle(FlockOrder::Scatter);
+        assert_eq!(order, FlockOrder::Follow);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::asset_plugin::{Objects, TriggerLoopAnimEvent};
-use crate::chunk_manager_plugin::{Chunk, ChunkChangeEvent, CHUNK_MID, FBLOCK_SIZE};
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::{ActiveGenProfile, ChunkChangeEvent, FBLOCK_SIZE};
+use crate::controls::{Action, ActionInput};
 use crate::follow_plugin::FollowTarget;
+use crate::health_plugin::SheepDeathEvent;
 use crate::menu_plugin::Menu;
 use crate::player_manager_plugin::Minion;
 use crate::player_manager_plugin::Player;
@@ -16,11 +19,35 @@ pub struct GamePlugin;
 
 pub struct NewGameEvent;
 
+/// How the world around the player is laid out
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GameMode {
+    /// A single lane of chunks along the x axis with walls either side
+    Lane,
+    /// Chunks stream in every direction and score is the distance travelled
+    /// from the start
+    OpenWorld,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Lane
+    }
+}
+
 pub struct Paused(pub bool);
 
 pub struct PauseEvent;
 
 pub struct CurrentScore(pub isize);
+
+/// How many sheep have died this game
+#[derive(Default)]
+pub struct SheepLost(pub usize);
+
+/// The difficulty zone of the chunk the player is in, starting from zero
+#[derive(Default)]
+pub struct CurrentZone(pub usize);
 pub struct HighScores(pub isize, pub isize);
 
 #[derive(Debug)]
@@ -30,14 +57,18 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<NewGameEvent>()
             .add_event::<PauseEvent>()
+            .init_resource::<GameMode>()
             .insert_resource(Paused(false))
             .insert_resource(CurrentScore(0))
+            .init_resource::<CurrentZone>()
+            .init_resource::<SheepLost>()
             .insert_resource(HighScores(0, 0))
             .insert_resource(GameTime(Stopwatch::new()))
             .add_system(init_game)
             .add_system(controls)
             .add_system(paused_check)
             .add_system(score_manager)
+            .add_system(zone_manager)
             .add_system(tick);
     }
 }
@@ -46,22 +77,34 @@ fn tick(time: Res<Time>, mut stopwatch: ResMut<GameTime>) {
     stopwatch.0.tick(time.delta());
 }
 
+fn zone_manager(
+    mut ev_chunk_change: EventReader<ChunkChangeEvent>,
+    profile: Res<ActiveGenProfile>,
+    mut zone: ResMut<CurrentZone>,
+) {
+    if let Some(ChunkChangeEvent { newchunk, .. }) = ev_chunk_change.iter().last() {
+        zone.0 = profile.0.zone(newchunk);
+    }
+}
+
 fn score_manager(
     mut ev_chunk_change: EventReader<ChunkChangeEvent>,
+    mut ev_death: EventReader<SheepDeathEvent>,
     mut scores: ResMut<CurrentScore>,
     mut high_scores: ResMut<HighScores>,
+    mut sheep_lost: ResMut<SheepLost>,
     mut ev_save: EventWriter<SaveEvent>,
+    game_mode: Res<GameMode>,
 ) {
-    if let Some(chunk) = ev_chunk_change
+    sheep_lost.0 += ev_death.iter().count();
+    if let Some(signed_pos) = ev_chunk_change
         .iter()
-        .map(|ChunkChangeEvent { newchunk, .. }| newchunk.0)
+        .map(|ChunkChangeEvent { newchunk, .. }| match *game_mode {
+            GameMode::Lane => newchunk.0 as isize,
+            GameMode::OpenWorld => newchunk.distance_from_origin().round() as isize,
+        })
         .max()
     {
-        let signed_pos = if chunk >= CHUNK_MID.0 {
-            (chunk - CHUNK_MID.0) as isize
-        } else {
-            -((CHUNK_MID.0 - chunk) as isize)
-        };
         scores.0 = if (signed_pos < scores.0) || (signed_pos > scores.0) || scores.0 == 0 {
             let is_new_high_sore = if signed_pos < high_scores.0 && signed_pos < 0 {
                 high_scores.0 = signed_pos;
@@ -90,8 +133,12 @@ fn init_game(
     mut ev_chunk_change: EventWriter<ChunkChangeEvent>,
     mut ev_new_game: EventReader<NewGameEvent>,
     mut ev_trigger_loop_anim: EventWriter<TriggerLoopAnimEvent>,
+    game_mode: Res<GameMode>,
+    mut sheep_lost: ResMut<SheepLost>,
 ) {
     for _ in ev_new_game.iter().last() {
+        sheep_lost.0 = 0;
+
         // light
         commands.insert_resource(AmbientLight {
             brightness: 1.,
@@ -151,20 +198,22 @@ fn init_game(
                 0.5,
                 FBLOCK_SIZE * 10.0,
             ));
-        // far collider
-        commands
-            .spawn()
-            .insert_bundle(TransformBundle::from(Transform::from_xyz(0.0, 0.0, -0.5)))
-            .insert(Collider::cuboid(FBLOCK_SIZE * 10.0, 50., 0.5));
-        // near collider
-        commands
-            .spawn()
-            .insert_bundle(TransformBundle::from(Transform::from_xyz(
-                0.0,
-                0.0,
-                FBLOCK_SIZE - 0.5,
-            )))
-            .insert(Collider::cuboid(FBLOCK_SIZE * 10.0, 50., 0.5));
+        if *game_mode == GameMode::Lane {
+            // far collider
+            commands
+                .spawn()
+                .insert_bundle(TransformBundle::from(Transform::from_xyz(0.0, 0.0, -0.5)))
+                .insert(Collider::cuboid(FBLOCK_SIZE * 10.0, 50., 0.5));
+            // near collider
+            commands
+                .spawn()
+                .insert_bundle(TransformBundle::from(Transform::from_xyz(
+                    0.0,
+                    0.0,
+                    FBLOCK_SIZE - 0.5,
+                )))
+                .insert(Collider::cuboid(FBLOCK_SIZE * 10.0, 50., 0.5));
+        }
         // ceiling collider
         commands
             .spawn()
@@ -175,10 +224,10 @@ fn init_game(
                 FBLOCK_SIZE * 10.0,
             ));
 
-        *chunk = CHUNK_MID.clone();
+        *chunk = Chunk::ORIGIN;
         ev_chunk_change.send(ChunkChangeEvent {
-            oldchunk: CHUNK_MID.clone(),
-            newchunk: CHUNK_MID.clone(),
+            oldchunk: Chunk::ORIGIN,
+            newchunk: Chunk::ORIGIN,
         });
     }
 }
@@ -202,12 +251,8 @@ fn paused_check(
     }
 }
 
-fn controls(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut ev_pause: EventWriter<PauseEvent>,
-    menu: Res<Menu>,
-) {
-    if keyboard_input.just_pressed(KeyCode::Escape) && *menu == Menu::Game {
+fn controls(controls: ActionInput, mut ev_pause: EventWriter<PauseEvent>, menu: Res<Menu>) {
+    if controls.just_pressed(Action::Pause) && *menu == Menu::Game {
         ev_pause.send(PauseEvent);
     }
 }
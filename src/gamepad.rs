@@ -0,0 +1,64 @@
+//! Reading from whichever gamepads are connected
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+/// Opens cages, like “E”
+pub const INTERACT: GamepadButtonType = GamepadButtonType::South;
+/// Makes the next follower the leader, like “Q”
+pub const SWITCH_LEADER: GamepadButtonType = GamepadButtonType::West;
+/// Jumps, like “Space”
+pub const JUMP: GamepadButtonType = GamepadButtonType::North;
+/// Pauses and resumes, like “Esc”
+pub const PAUSE: GamepadButtonType = GamepadButtonType::Start;
+/// Uses the selected menu item
+pub const SELECT: GamepadButtonType = GamepadButtonType::South;
+/// Leaves a menu
+pub const BACK: GamepadButtonType = GamepadButtonType::East;
+
+/// The state of every connected gamepad, treated as if they were one
+#[derive(SystemParam)]
+pub struct GamepadInput<'w, 's> {
+    gamepads: Res<'w, Gamepads>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> GamepadInput<'w, 's> {
+    /// How far the left stick is tilted, with up as positive y, from the
+    /// first gamepad whose stick is out of its dead zone
+    pub fn left_stick(&self) -> Vec2 {
+        self.gamepads
+            .iter()
+            .map(|gamepad| {
+                Vec2::new(
+                    self.axes
+                        .get(GamepadAxis(*gamepad, GamepadAxisType::LeftStickX))
+                        .unwrap_or(0.),
+                    self.axes
+                        .get(GamepadAxis(*gamepad, GamepadAxisType::LeftStickY))
+                        .unwrap_or(0.),
+                )
+            })
+            .find(|tilt| *tilt != Vec2::ZERO)
+            .unwrap_or(Vec2::ZERO)
+            .clamp_length_max(1.)
+    }
+
+    /// Whether a button is held down on any gamepad
+    pub fn pressed(&self, button: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.buttons.pressed(GamepadButton(*gamepad, button)))
+    }
+
+    /// Whether a button has just been pressed on any gamepad
+    pub fn just_pressed(&self, button: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.buttons.just_pressed(GamepadButton(*gamepad, button)))
+    }
+}
@@ -0,0 +1,121 @@
+//! Tiles that get in the way of sheep when they touch them: water slows them
+//! down and carries them along, lava kills them and spikes throw them back.
+
+use crate::health_plugin::{DamageCause, DamageEvent};
+use crate::player_manager_plugin::{Minion, Player};
+use crate::share::TileType;
+use bevy::app::Plugin;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::HashSet;
+
+pub struct HazardPlugin;
+
+/// How much water slows down a sheep, in place of its biome's damping
+const WATER_DAMPING: f32 = 4.;
+
+/// Which way and how strongly the water carries sheep
+const WATER_DRIFT: Vec3 = Vec3::new(3., 0., 1.);
+
+/// How fast spikes throw a sheep back from them
+const SPIKE_BOUNCE: f32 = 6.;
+
+/// How much health a sheep loses each time it lands on spikes
+const SPIKE_DAMAGE: u32 = 1;
+
+/// A sensor over a hazard tile
+#[derive(Component, Copy, Clone, Debug)]
+pub struct Hazard(pub TileType);
+
+/// The water sensors that a sheep is touching
+#[derive(Component, Default)]
+pub struct Wading(HashSet<Entity>);
+
+impl Wading {
+    pub fn in_water(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(hazard_contact.label("hazards").before("damage"))
+            .add_system(water_effects.after("hazards"));
+    }
+}
+
+fn hazard_contact(
+    mut ev_collision: EventReader<CollisionEvent>,
+    mut ev_damage: EventWriter<DamageEvent>,
+    hazards: Query<(&Hazard, &Transform)>,
+    mut sheep: Query<(&Transform, &mut Velocity, &mut Wading), Or<(&Player, &Minion)>>,
+) {
+    for ev in ev_collision.iter() {
+        let (a, b, started) = match ev {
+            CollisionEvent::Started(a, b, _) => (*a, *b, true),
+            CollisionEvent::Stopped(a, b, _) => (*a, *b, false),
+        };
+        // Either side of the collision could be the sensor
+        let (sensor, ent) = match hazards.get(a) {
+            Ok(_) => (a, b),
+            Err(_) => (b, a),
+        };
+        let (hazard, hazard_transform) = match hazards.get(sensor) {
+            Ok(hazard) => hazard,
+            Err(_) => continue,
+        };
+        let (transform, mut velocity, mut wading) = match sheep.get_mut(ent) {
+            Ok(sheep) => sheep,
+            Err(_) => continue,
+        };
+
+        match (hazard.0, started) {
+            (TileType::Water, true) => {
+                wading.0.insert(sensor);
+            }
+            (TileType::Water, false) => {
+                wading.0.remove(&sensor);
+            }
+            (TileType::Lava, true) => {
+                ev_damage.send(DamageEvent {
+                    target: ent,
+                    amount: 0,
+                    cause: DamageCause::Lava,
+                });
+            }
+            (TileType::Spikes, true) => {
+                let away =
+                    (transform.translation - hazard_transform.translation) * Vec3::new(1., 0., 1.);
+                let away = away
+                    .try_normalize()
+                    .unwrap_or(-velocity.linvel.normalize_or_zero());
+                velocity.linvel = away * SPIKE_BOUNCE + Vec3::Y * SPIKE_BOUNCE / 2.;
+                ev_damage.send(DamageEvent {
+                    target: ent,
+                    amount: SPIKE_DAMAGE,
+                    cause: DamageCause::Spikes,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Slow down sheep in water and carry them along with it
+fn water_effects(
+    mut sheep: Query<(&mut Wading, &mut Velocity, &mut Damping)>,
+    hazards: Query<&Hazard>,
+    time: Res<Time>,
+) {
+    for (mut wading, mut velocity, mut damping) in sheep.iter_mut() {
+        // Sensors can be despawned with the sheep still in them when their
+        // chunk is unloaded or edited
+        wading.0.retain(|sensor| hazards.get(*sensor).is_ok());
+        if wading.in_water() {
+            if damping.linear_damping != WATER_DAMPING {
+                damping.linear_damping = WATER_DAMPING;
+            }
+            velocity.linvel += WATER_DRIFT * time.delta_seconds();
+        }
+    }
+}
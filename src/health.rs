@@ -0,0 +1,118 @@
+//! How much of a beating a sheep can take before it is gone
+
+use bevy::prelude::*;
+
+/// How many hits a sheep can take
+pub const SHEEP_HEALTH: u32 = 3;
+
+/// How long after being hurt before a sheep can be hurt again
+pub const INVULNERABLE_TIME: f64 = 1.;
+
+/// How much damage a sheep takes right next to a blast, which drops off to
+/// nothing at the edge of it
+pub const BLAST_DAMAGE: u32 = SHEEP_HEALTH;
+
+/// What happened to a sheep that was damaged
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Harm {
+    /// It was already dead, or still getting over the last hit
+    Ignored,
+    Hurt,
+    Killed,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Health {
+    hp: u32,
+    /// When the sheep was last hurt, in seconds since startup
+    hurt_at: Option<f64>,
+}
+
+impl Health {
+    pub fn new(hp: u32) -> Self {
+        Health { hp, hurt_at: None }
+    }
+
+    pub fn hp(&self) -> u32 {
+        self.hp
+    }
+
+    /// How long ago the sheep was hurt, if it can't be hurt again yet
+    pub fn hurt_for(&self, now: f64) -> Option<f64> {
+        self.hurt_at
+            .map(|hurt_at| now - hurt_at)
+            .filter(|&since| since < INVULNERABLE_TIME)
+    }
+
+    pub fn damage(&mut self, amount: u32, now: f64) -> Harm {
+        if self.hp == 0 || amount == 0 || self.hurt_for(now).is_some() {
+            return Harm::Ignored;
+        }
+        self.hp = self.hp.saturating_sub(amount);
+        self.hurt_at = Some(now);
+        match self.hp {
+            0 => Harm::Killed,
+            _ => Harm::Hurt,
+        }
+    }
+
+    /// Kill the sheep outright, however recently it was hurt
+    pub fn kill(&mut self) -> Harm {
+        match std::mem::take(&mut self.hp) {
+            0 => Harm::Ignored,
+            _ => Harm::Killed,
+        }
+    }
+}
+
+/// How much damage a blast of `radius` does to a sheep `distance` away from it
+pub fn blast_damage(distance: f32, radius: f32) -> u32 {
+    if distance > radius || radius <= 0. {
+        return 0;
+    }
+    (BLAST_DAMAGE as f32 * (1. - distance / radius)).ceil() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blasts_hurt_less_further_away() {
+        assert_eq!(blast_damage(0., 3.), BLAST_DAMAGE);
+        assert_eq!(blast_damage(1.5, 3.), 2);
+        assert_eq!(blast_damage(2.9, 3.), 1);
+        assert_eq!(blast_damage(3.1, 3.), 0);
+        let mut last = BLAST_DAMAGE;
+        for step in 0..=30 {
+            let damage = blast_damage(step as f32 / 10., 3.);
+            assert!(damage <= last);
+            last = damage;
+        }
+    }
+
+    #[test]
+    fn sheep_get_a_moment_to_recover() {
+        let mut health = Health::new(SHEEP_HEALTH);
+        assert_eq!(health.damage(1, 10.), Harm::Hurt);
+        assert_eq!(health.damage(1, 10.5), Harm::Ignored);
+        assert_eq!(health.hp(), SHEEP_HEALTH - 1);
+        assert!(health.hurt_for(10.5).is_some());
+        assert_eq!(health.hurt_for(11.), None);
+        assert_eq!(health.damage(1, 11.), Harm::Hurt);
+    }
+
+    #[test]
+    fn sheep_only_die_once() {
+        let mut health = Health::new(2);
+        assert_eq!(health.damage(5, 0.), Harm::Killed);
+        assert_eq!(health.hp(), 0);
+        assert_eq!(health.damage(1, 5.), Harm::Ignored);
+        assert_eq!(health.kill(), Harm::Ignored);
+
+        // Being hurt doesn't save a sheep from being killed outright
+        let mut health = Health::new(SHEEP_HEALTH);
+        assert_eq!(health.damage(1, 0.), Harm::Hurt);
+        assert_eq!(health.kill(), Harm::Killed);
+    }
+}
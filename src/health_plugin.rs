@@ -0,0 +1,97 @@
+//! Damage to sheep from blasts and hazards, with a moment to recover after
+//! each hit, and what happens when a sheep runs out of health
+
+use crate::health::{Harm, Health};
+use crate::player_manager_plugin::Player;
+use bevy::app::Plugin;
+use bevy::prelude::*;
+
+pub struct HealthPlugin;
+
+/// How many times a second a hurt sheep blinks
+const BLINK_RATE: f64 = 8.;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DamageCause {
+    Explosion,
+    Spikes,
+    Lava,
+    /// Taken out of the game with the debug controls
+    Removed,
+}
+
+impl DamageCause {
+    /// Whether it kills a sheep outright, however much health it has left
+    fn is_lethal(self) -> bool {
+        matches!(self, DamageCause::Lava | DamageCause::Removed)
+    }
+}
+
+pub struct DamageEvent {
+    pub target: Entity,
+    /// Left out for causes that kill outright
+    pub amount: u32,
+    pub cause: DamageCause,
+}
+
+/// Sent when a sheep dies, just before it is despawned
+pub struct SheepDeathEvent {
+    pub entity: Entity,
+    pub pos: Vec3,
+    pub was_leader: bool,
+}
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<SheepDeathEvent>()
+            .add_system(apply_damage.label("damage"))
+            .add_system(hurt_flash.after("damage"));
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut ev_damage: EventReader<DamageEvent>,
+    mut ev_death: EventWriter<SheepDeathEvent>,
+    mut sheep: Query<(&mut Health, &Transform, Option<&Player>)>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for DamageEvent {
+        target,
+        amount,
+        cause,
+    } in ev_damage.iter()
+    {
+        let (mut health, transform, player) = match sheep.get_mut(*target) {
+            Ok(sheep) => sheep,
+            Err(_) => continue,
+        };
+        let harm = match cause.is_lethal() {
+            true => health.kill(),
+            false => health.damage(*amount, now),
+        };
+        if harm == Harm::Killed {
+            ev_death.send(SheepDeathEvent {
+                entity: *target,
+                pos: transform.translation,
+                was_leader: player.is_some(),
+            });
+            commands.entity(*target).despawn_recursive();
+        }
+    }
+}
+
+/// Blink sheep that have just been hurt, while they can't be hurt again
+fn hurt_flash(mut sheep: Query<(&Health, &mut Visibility)>, time: Res<Time>) {
+    let now = time.seconds_since_startup();
+    for (health, mut visibility) in sheep.iter_mut() {
+        let visible = health
+            .hurt_for(now)
+            .map_or(true, |since| (since * BLINK_RATE * 2.) as u64 % 2 == 1);
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use crate::asset_plugin::{Objects, TriggerLoopAnimEvent};
+use crate::chunk_manager_plugin::{ActiveGenProfile, TerrainChangeEvent};
 use crate::game_plugin::GameTime;
+use crate::health::{blast_damage, Health};
+use crate::health_plugin::{DamageCause, DamageEvent};
 use crate::player_manager_plugin::{Minion, Player};
-use crate::share::{DynamicPos, Indestructible, Terrain};
+use crate::share::{DynamicPos, Indestructible, Terrain, TileRef};
 use crate::sound_plugin::{Effect, SoundEffectEvent};
 use crate::templates;
 use bevy::app::Plugin;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::ExternalImpulse;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 pub struct ItemPlugin;
@@ -22,7 +26,13 @@ pub struct EquipGiveEvent {
     pub item: ItemType,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Sent when a bomb goes off so that the terrain around it can be destroyed
+pub struct ExplosionEvent {
+    pub pos: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ItemType {
     Cage,
     Launcher,
@@ -32,29 +42,45 @@ pub enum ItemType {
 #[derive(Component, Debug)]
 struct Cooldown(Duration);
 
+/// How far away from a bomb things are caught in its blast
+const BLAST_RADIUS: f32 = 3.;
+
+/// How long after being fired a bomb goes off
+#[derive(Component, Debug)]
+struct Fuse(Duration);
+
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<EquipTakeEvent>()
             .add_event::<EquipGiveEvent>()
+            .add_event::<ExplosionEvent>()
             .add_system(laucher_ai.label("laucher_ai"))
-            .add_system(bomb_ai.after("laucher_ai"))
+            .add_system(bomb_ai.after("laucher_ai").before("damage"))
             .add_system(equip_manager);
     }
 }
 
 fn equip_manager(
     mut ev_equip_take: EventReader<EquipTakeEvent>,
-    items: Query<(Entity, &Transform, &Item)>,
+    items: Query<(
+        Entity,
+        &Transform,
+        &Item,
+        Option<&Terrain>,
+        Option<&TileRef>,
+    )>,
     mut commands: Commands,
     objects: Res<Objects>,
     mut ev_trigger_loop_anim: EventWriter<TriggerLoopAnimEvent>,
     mut ev_effect: EventWriter<SoundEffectEvent>,
+    mut ev_terrain_change: EventWriter<TerrainChangeEvent>,
 ) {
     for EquipTakeEvent { pos, reach } in ev_equip_take.iter().last() {
-        for (ent, transform, Item(kind)) in items.iter() {
+        for (ent, transform, Item(kind), terrain, tile) in items.iter() {
             if transform.translation.distance(*pos) < *reach {
                 match *kind {
                     ItemType::Cage => {
+                        record_terrain_change(&mut ev_terrain_change, terrain, tile);
                         cage_despawn_helper(
                             &mut commands,
                             &mut ev_trigger_loop_anim,
@@ -71,6 +97,21 @@ fn equip_manager(
     }
 }
 
+/// Remember that a generated part of the world has gone so that it isn't
+/// brought back when its chunk is reloaded
+fn record_terrain_change(
+    ev_terrain_change: &mut EventWriter<TerrainChangeEvent>,
+    terrain: Option<&Terrain>,
+    tile: Option<&TileRef>,
+) {
+    if let (Some(Terrain(chunk)), Some(tile)) = (terrain, tile) {
+        ev_terrain_change.send(TerrainChangeEvent {
+            chunk: *chunk,
+            tile: *tile,
+        });
+    }
+}
+
 fn cage_despawn_helper(
     commands: &mut Commands,
     ev_trigger_loop_anim: &mut EventWriter<TriggerLoopAnimEvent>,
@@ -91,14 +132,21 @@ fn cage_despawn_helper(
 }
 
 fn laucher_ai(
-    items: Query<(Entity, &Item, &Transform, Option<&Cooldown>)>,
+    items: Query<(
+        Entity,
+        &Item,
+        &Transform,
+        Option<&Cooldown>,
+        Option<&Terrain>,
+    )>,
     targets: Query<&Transform, Or<(&Player, &Minion)>>,
     mut commands: Commands,
     objects: Res<Objects>,
     time: Res<GameTime>,
+    profile: Res<ActiveGenProfile>,
     mut ev_effect: EventWriter<SoundEffectEvent>,
 ) {
-    for (ent, _, trans, cooldown) in items.iter().filter(|(_, it, _, _)| match it.0 {
+    for (ent, _, trans, cooldown, terrain) in items.iter().filter(|(_, it, _, _, _)| match it.0 {
         ItemType::Launcher => true,
         _ => false,
     }) {
@@ -128,7 +176,15 @@ fn laucher_ai(
                             * 7.,
                         ..default()
                     })
-                    .insert(Cooldown(time.0.elapsed()));
+                    .insert(Cooldown(time.0.elapsed()))
+                    .insert(Fuse(Duration::from_secs_f32(
+                        // Launchers further from the start fire quicker bombs
+                        profile.0.fuse(
+                            terrain
+                                .map(|Terrain(chunk)| profile.0.zone(chunk))
+                                .unwrap_or(0),
+                        ),
+                    )));
                 break;
             }
         }
@@ -136,9 +192,16 @@ fn laucher_ai(
 }
 
 fn bomb_ai(
-    bombs: Query<(Entity, &Item, &Transform, &Cooldown, &Children)>,
+    bombs: Query<(Entity, &Item, &Transform, &Cooldown, &Fuse, &Children)>,
     destruct: Query<
-        (Entity, &Transform, Option<&Item>),
+        (
+            Entity,
+            &Transform,
+            Option<&Item>,
+            Option<&Terrain>,
+            Option<&TileRef>,
+            Option<&Health>,
+        ),
         (Or<(&Terrain, &Player, &Minion)>, Without<Indestructible>),
     >,
     time: Res<GameTime>,
@@ -147,25 +210,47 @@ fn bomb_ai(
     mut light: Query<&mut PointLight>,
     mut ev_effect: EventWriter<SoundEffectEvent>,
     mut ev_trigger_loop_anim: EventWriter<TriggerLoopAnimEvent>,
+    mut ev_terrain_change: EventWriter<TerrainChangeEvent>,
+    mut ev_explosion: EventWriter<ExplosionEvent>,
+    mut ev_damage: EventWriter<DamageEvent>,
 ) {
-    for (ent, _, trans, cooldown, children) in bombs.iter().filter(|(_, it, _, _, _)| match it.0 {
-        ItemType::Bomb => true,
-        _ => false,
-    }) {
+    for (ent, _, trans, cooldown, fuse, children) in
+        bombs.iter().filter(|(_, it, _, _, _, _)| match it.0 {
+            ItemType::Bomb => true,
+            _ => false,
+        })
+    {
         let delta = time.0.elapsed() - cooldown.0;
-        if delta >= Duration::from_secs_f32(7.) {
+        // Warn the player for the last few seconds
+        if delta + Duration::from_secs_f32(3.) >= fuse.0 {
             for child in children.iter() {
                 if let Ok(mut light) = light.get_mut(*child) {
                     light.color = Color::BLUE;
                 }
             }
         }
-        if delta >= Duration::from_secs_f32(10.) {
+        if delta >= fuse.0 {
             ev_effect.send(SoundEffectEvent {
                 effect: Effect::BombZap,
             });
-            for (dent, dtrans, item) in destruct.iter() {
-                if dtrans.translation.distance(trans.translation) <= 3. {
+            ev_explosion.send(ExplosionEvent {
+                pos: trans.translation,
+                radius: BLAST_RADIUS,
+            });
+            for (dent, dtrans, item, terrain, tile, health) in destruct.iter() {
+                let distance = dtrans.translation.distance(trans.translation);
+                if distance > BLAST_RADIUS {
+                    continue;
+                }
+                if health.is_some() {
+                    // Sheep are hurt less the further they are from the blast
+                    ev_damage.send(DamageEvent {
+                        target: dent,
+                        amount: blast_damage(distance, BLAST_RADIUS),
+                        cause: DamageCause::Explosion,
+                    });
+                } else {
+                    record_terrain_change(&mut ev_terrain_change, terrain, tile);
                     if item
                         .map(|item| match item.0 {
                             ItemType::Cage => true,
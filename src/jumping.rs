@@ -0,0 +1,141 @@
+//! When sheep are allowed to jump, so that being on the ground, a moment of
+//! grace after running off an edge and running out of puff are all handled
+//! in one place
+
+use bevy::prelude::*;
+
+/// How long after leaving the ground a sheep can still jump
+const COYOTE_TIME: f64 = 0.15;
+
+/// How long after a jump before a sheep can jump again, which also stops the
+/// ground it has only just left from counting
+const JUMP_COOLDOWN: f64 = 0.4;
+
+/// How many jumps a rested sheep can make in a row
+pub const MAX_STAMINA: f32 = 3.;
+
+/// How much stamina each jump uses
+const JUMP_COST: f32 = 1.;
+
+/// How much stamina comes back each second that a sheep is on the ground
+const STAMINA_REGEN: f32 = 0.75;
+
+#[derive(Component, Debug, Clone)]
+pub struct JumpBudget {
+    /// When the sheep was last on the ground, in seconds since startup
+    last_grounded: Option<f64>,
+    last_jump: Option<f64>,
+    stamina: f32,
+}
+
+impl Default for JumpBudget {
+    fn default() -> Self {
+        JumpBudget {
+            last_grounded: None,
+            last_jump: None,
+            stamina: MAX_STAMINA,
+        }
+    }
+}
+
+impl JumpBudget {
+    pub fn stamina(&self) -> f32 {
+        self.stamina
+    }
+
+    /// Keep track of whether the sheep is on the ground, `dt` seconds after
+    /// the last update
+    pub fn update(&mut self, grounded: bool, now: f64, dt: f32) {
+        let rising = self
+            .last_jump
+            .map_or(false, |jump| now - jump < JUMP_COOLDOWN);
+        if grounded && !rising {
+            self.last_grounded = Some(now);
+            self.stamina = (self.stamina + STAMINA_REGEN * dt).min(MAX_STAMINA);
+        }
+    }
+
+    pub fn can_jump(&self, now: f64) -> bool {
+        self.last_grounded
+            .map_or(false, |grounded| now - grounded <= COYOTE_TIME)
+            && self
+                .last_jump
+                .map_or(true, |jump| now - jump >= JUMP_COOLDOWN)
+            && self.stamina >= JUMP_COST
+    }
+
+    /// Use up a jump if there is one, returning whether the sheep jumped
+    pub fn try_jump(&mut self, now: f64) -> bool {
+        if !self.can_jump(now) {
+            return false;
+        }
+        self.stamina -= JUMP_COST;
+        self.last_jump = Some(now);
+        // Only one jump per time on the ground
+        self.last_grounded = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 1. / 60.;
+
+    #[test]
+    fn only_jumps_from_the_ground() {
+        let mut budget = JumpBudget::default();
+        budget.update(false, 0., FRAME);
+        assert!(!budget.try_jump(0.));
+        budget.update(true, 1., FRAME);
+        assert!(budget.try_jump(1.));
+        // No jumping again in mid-air
+        budget.update(false, 1.5, FRAME);
+        assert!(!budget.try_jump(1.5));
+    }
+
+    #[test]
+    fn can_jump_just_after_running_off_an_edge() {
+        let mut budget = JumpBudget::default();
+        budget.update(true, 1., FRAME);
+        budget.update(false, 1.1, FRAME);
+        assert!(budget.can_jump(1.1));
+        budget.update(false, 1.2, FRAME);
+        assert!(!budget.can_jump(1.2));
+    }
+
+    #[test]
+    fn the_ground_just_left_does_not_count() {
+        let mut budget = JumpBudget::default();
+        budget.update(true, 1., FRAME);
+        assert!(budget.try_jump(1.));
+        // Still touching the ground on the way up
+        budget.update(true, 1. + FRAME as f64, FRAME);
+        assert!(!budget.try_jump(1. + FRAME as f64));
+        budget.update(true, 1.5, FRAME);
+        assert!(budget.try_jump(1.5));
+    }
+
+    #[test]
+    fn jumping_tires_sheep_out() {
+        let mut budget = JumpBudget::default();
+        let mut now = 0.;
+        let mut jumps = 0;
+        for _ in 0..5 {
+            budget.update(true, now, FRAME);
+            jumps += budget.try_jump(now) as usize;
+            now += JUMP_COOLDOWN;
+        }
+        assert_eq!(jumps, 3);
+        assert!(budget.stamina() < JUMP_COST);
+
+        // Resting on the ground brings the stamina back
+        for _ in 0..=(60. * MAX_STAMINA / STAMINA_REGEN) as usize {
+            now += FRAME as f64;
+            budget.update(true, now, FRAME);
+        }
+        assert_eq!(budget.stamina(), MAX_STAMINA);
+        assert!(budget.try_jump(now));
+    }
+}
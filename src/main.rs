@@ -1,5 +1,4 @@
-#![feature(mixed_integer_ops)]
-
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 use bevy_kira_audio::prelude::*;
@@ -8,11 +7,38 @@ use bevy_rapier3d::prelude::*;
 
 mod share;
 
+mod gamepad;
+
+mod controls;
+
+mod chunk;
+
+mod streaming;
+
+mod flocking;
+
+mod pathfinding;
+
+mod jumping;
+
+mod health;
+
 mod templates;
 
+mod terrain_mesh;
+
 mod debug_plugin;
 use debug_plugin::DebugPlugin;
 
+#[cfg(build = "debug")]
+mod editor_plugin;
+
+mod hazard_plugin;
+use hazard_plugin::HazardPlugin;
+
+mod health_plugin;
+use health_plugin::HealthPlugin;
+
 mod chunk_manager_plugin;
 use chunk_manager_plugin::ChunkManagerPlugin;
 
@@ -43,14 +69,36 @@ use settings_plugin::SettingsPlugin;
 mod tutorial_plugin;
 use tutorial_plugin::TutorialPlugin;
 
+mod world_gen;
+
+mod worldgen_cli;
+
 fn main() {
+    // Generate chunks without starting the game
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("worldgen") {
+        if let Err(e) = worldgen_cli::run(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     App::new()
+        // Lets world generation profiles be tuned while the game is running,
+        // which is only wanted while developing
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(build = "debug"),
+            ..default()
+        })
         .add_plugins(DefaultPlugins)
         .add_plugin(PickingPlugin)
         .add_plugin(InteractablePickingPlugin)
         .add_plugin(FollowPlugin)
         .add_plugin(ChunkManagerPlugin)
         .add_plugin(PlayerManagerPlugin)
+        .add_plugin(HazardPlugin)
+        .add_plugin(HealthPlugin)
         .add_plugin(ItemPlugin)
         .add_plugin(EguiPlugin)
         .add_plugin(AssetPlugin)
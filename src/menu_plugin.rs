@@ -1,10 +1,24 @@
-use crate::game_plugin::{CurrentScore, HighScores, NewGameEvent, PauseEvent, Paused};
+use crate::chunk_manager_plugin::{
+    RenderDistance, SelectedGenProfile, MAX_RENDER_DISTANCE, MIN_RENDER_DISTANCE,
+};
+use crate::controls::{key_name, Action, Bindings, KEYS_PER_ACTION};
+use crate::flocking::FlockOrder;
+use crate::game_plugin::{
+    CurrentScore, CurrentZone, GameMode, HighScores, NewGameEvent, PauseEvent, Paused, SheepLost,
+};
+use crate::gamepad::{self, GamepadInput};
+use crate::health::{Health, SHEEP_HEALTH};
+use crate::player_manager_plugin::Player;
 use crate::settings_plugin::SaveEvent;
 use crate::sound_plugin::{EffectsVolume, MusicVolume, PlayMusic};
 use crate::tutorial_plugin::{ShowTutorial, ShowTutorials, Tutorial};
+use crate::world_gen::GEN_PRESETS;
 use bevy::app::AppExit;
 use bevy::app::Plugin;
-use bevy::prelude::{App, EventWriter, Res, ResMut};
+use bevy::prelude::{
+    App, EventWriter, GamepadButtonType, Input, KeyCode, Local, MouseButton,
+    ParallelSystemDescriptorCoercion, Query, Res, ResMut, With,
+};
 use bevy_egui::egui::*;
 use bevy_egui::*;
 
@@ -13,9 +27,145 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Menu>()
-            .add_system(menu)
+            .init_resource::<MenuNav>()
+            .init_resource::<Bindings>()
+            .init_resource::<Rebinding>()
+            .add_system(gamepad_menu_nav.label("menu_nav"))
+            .add_system(capture_rebinding.label("rebinding"))
+            .add_system(menu.after("menu_nav").after("rebinding"))
             .add_system(hud)
-            .add_system(pause_menu);
+            .add_system(pause_menu.after("menu_nav"));
+    }
+}
+
+/// How far one push of the D-pad moves a volume slider
+const VOLUME_STEP: f32 = 10.;
+
+/// Lets the D-pad move between the widgets in the menus and use them.
+///
+/// Widgets are counted as they are drawn, so the selection is an index into
+/// the order that they appear on screen.
+#[derive(Default)]
+struct MenuNav {
+    /// Whether the menus are being used with a gamepad, so that the selection
+    /// only shows up once the D-pad has been pressed
+    active: bool,
+    selected: usize,
+    /// How many widgets have been drawn so far this frame
+    drawn: usize,
+    activate: bool,
+    back: bool,
+    /// Which way the selected slider or list is being pushed
+    nudge: i32,
+}
+
+impl MenuNav {
+    /// Count a widget, returning whether it is the selected one
+    fn visit(&mut self, response: &Response) -> bool {
+        let selected = self.active && self.drawn == self.selected;
+        self.drawn += 1;
+        if selected && !response.has_focus() {
+            response.request_focus();
+        }
+        selected
+    }
+
+    /// Whether the widget was used from the gamepad
+    fn activated(&mut self, response: &Response) -> bool {
+        self.visit(response) && self.activate
+    }
+
+    /// Whether a button was clicked or used from the gamepad
+    fn clicked(&mut self, response: Response) -> bool {
+        self.activated(&response) || response.clicked()
+    }
+
+    /// Which way the gamepad is pushing a slider or list
+    fn nudged(&mut self, response: &Response) -> i32 {
+        match self.visit(response) {
+            true => self.nudge,
+            false => 0,
+        }
+    }
+}
+
+fn gamepad_menu_nav(
+    gamepads: GamepadInput,
+    mouse_input: Res<Input<MouseButton>>,
+    menu: Res<Menu>,
+    paused: Res<Paused>,
+    mut nav: ResMut<MenuNav>,
+    mut last_screen: Local<Option<(Menu, bool)>>,
+) {
+    let count = std::mem::take(&mut nav.drawn);
+
+    // Start from the top whenever a different menu is shown
+    let screen = (*menu, paused.0);
+    if *last_screen != Some(screen) {
+        *last_screen = Some(screen);
+        nav.selected = 0;
+    }
+    if mouse_input.get_just_pressed().next().is_some() {
+        nav.active = false;
+    }
+
+    let up = gamepads.just_pressed(GamepadButtonType::DPadUp);
+    let down = gamepads.just_pressed(GamepadButtonType::DPadDown);
+    if (up || down) && count > 0 {
+        // The first press only shows where the selection is
+        if nav.active {
+            nav.selected = match up {
+                true => (nav.selected + count - 1) % count,
+                false => (nav.selected + 1) % count,
+            };
+        }
+        nav.active = true;
+    }
+    nav.selected = nav.selected.min(count.saturating_sub(1));
+    nav.activate = nav.active && gamepads.just_pressed(gamepad::SELECT);
+    nav.back = gamepads.just_pressed(gamepad::BACK);
+    nav.nudge = match nav.active {
+        true => {
+            gamepads.just_pressed(GamepadButtonType::DPadRight) as i32
+                - gamepads.just_pressed(GamepadButtonType::DPadLeft) as i32
+        }
+        false => 0,
+    };
+}
+
+/// The key binding that is waiting for a key to be pressed, and what came of
+/// the last change
+#[derive(Default)]
+struct Rebinding {
+    waiting: Option<(Action, usize)>,
+    /// Set on the frame a key is bound, since pressing Space or Enter would
+    /// otherwise click the selected button again
+    just_bound: bool,
+    message: String,
+}
+
+fn capture_rebinding(
+    keyboard_input: Res<Input<KeyCode>>,
+    menu: Res<Menu>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+    mut ev_save: EventWriter<SaveEvent>,
+) {
+    rebinding.just_bound = false;
+    if *menu != Menu::Options {
+        rebinding.waiting = None;
+        return;
+    }
+    let key = keyboard_input.get_just_pressed().next();
+    if let (Some((action, slot)), Some(&key)) = (rebinding.waiting, key) {
+        rebinding.waiting = None;
+        rebinding.just_bound = true;
+        rebinding.message = match bindings.rebind(action, slot, key) {
+            Ok(Some(other)) => format!("{} now uses {}", other.name(), bindings.describe(other)),
+            Ok(None) => String::new(),
+            Err(e) => e,
+        };
+        ev_save.send(SaveEvent);
     }
 }
 
@@ -44,6 +194,9 @@ fn hud(
     mut play_music: ResMut<PlayMusic>,
     menu: ResMut<Menu>,
     current_score: Res<CurrentScore>,
+    current_zone: Res<CurrentZone>,
+    mut flock_order: ResMut<FlockOrder>,
+    leader: Query<&Health, With<Player>>,
 ) {
     if *menu == Menu::Game {
         TopBottomPanel::top("hud").show(egui_context.ctx_mut(), |ui| {
@@ -55,6 +208,30 @@ fn hud(
                     format!("Score: {}", current_score.0).as_str(),
                     None,
                 ));
+                ui.label(sized_text(
+                    format!("Zone: {}", current_zone.0 + 1).as_str(),
+                    None,
+                ));
+                for health in leader.iter() {
+                    let hearts = (0..SHEEP_HEALTH)
+                        .map(|i| if i < health.hp() { '♥' } else { '♡' })
+                        .collect::<String>();
+                    ui.label(sized_text(hearts.as_str(), None));
+                }
+                // Orders can be given here without a keyboard, and the one
+                // that has been given stays highlighted
+                for (order, name) in [
+                    (FlockOrder::Hold, "Hold"),
+                    (FlockOrder::Regroup, "Regroup"),
+                    (FlockOrder::Scatter, "Scatter"),
+                ] {
+                    if ui
+                        .selectable_label(*flock_order == order, sized_text(name, None))
+                        .clicked()
+                    {
+                        flock_order.toggle(order);
+                    }
+                }
             });
         });
         play_music.0 = true;
@@ -73,22 +250,40 @@ fn menu(
     mut effects_volume: ResMut<EffectsVolume>,
     mut show_tutorials: ResMut<ShowTutorials>,
     mut ev_save: EventWriter<SaveEvent>,
-    current_score: Res<CurrentScore>,
+    (current_score, sheep_lost): (Res<CurrentScore>, Res<SheepLost>),
+    mut game_mode: ResMut<GameMode>,
+    mut gen_profile: ResMut<SelectedGenProfile>,
+    mut render_distance: ResMut<RenderDistance>,
+    mut nav: ResMut<MenuNav>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
 ) {
     let mut m = *menu;
     if &m != &Menu::Game {
         CentralPanel::default().show(egui_context.ctx_mut(), |ui| match m {
-            Menu::Main => menu_main(ui, &mut m, &mut ev_new_game, &mut exit),
+            Menu::Main => menu_main(
+                ui,
+                &mut m,
+                &mut game_mode,
+                &mut ev_new_game,
+                &mut exit,
+                &mut nav,
+            ),
             Menu::Options => menu_options(
                 ui,
                 &mut m,
                 &mut music_volume,
                 &mut effects_volume,
                 &mut show_tutorials,
+                &mut gen_profile,
+                &mut render_distance,
+                &mut bindings,
+                &mut rebinding,
                 &mut ev_save,
+                &mut nav,
             ),
-            Menu::Credits => menu_credits(ui, &mut m),
-            Menu::GameOver => menu_game_over(ui, &current_score),
+            Menu::Credits => menu_credits(ui, &mut m, &mut nav),
+            Menu::GameOver => menu_game_over(ui, &current_score, &sheep_lost),
             _ => (),
         });
         TopBottomPanel::bottom("scores").show(egui_context.ctx_mut(), |ui| {
@@ -108,43 +303,42 @@ fn menu(
 fn menu_main(
     ui: &mut Ui,
     menu: &mut Menu,
+    game_mode: &mut GameMode,
     ev_new_game: &mut EventWriter<NewGameEvent>,
     exit: &mut EventWriter<AppExit>,
+    nav: &mut MenuNav,
 ) {
     ui.vertical_centered(|ui| {
         ui.label(sized_text("Flocked and Loaded", Some(60.)));
-        if ui
-            .add_sized(
-                [200.0, 100.0],
-                egui::Button::new(sized_text("New Game", None)),
-            )
-            .clicked()
-        {
+        if nav.clicked(ui.add_sized(
+            [200.0, 100.0],
+            egui::Button::new(sized_text("New Game", None)),
+        )) {
+            *menu = Menu::Game;
+            *game_mode = GameMode::Lane;
+            ev_new_game.send(NewGameEvent);
+        }
+        if nav.clicked(ui.add_sized(
+            [200.0, 100.0],
+            egui::Button::new(sized_text("Open World", None)),
+        )) {
             *menu = Menu::Game;
+            *game_mode = GameMode::OpenWorld;
             ev_new_game.send(NewGameEvent);
         }
-        if ui
-            .add_sized(
-                [200.0, 100.0],
-                egui::Button::new(sized_text("Credits", None)),
-            )
-            .clicked()
-        {
+        if nav.clicked(ui.add_sized(
+            [200.0, 100.0],
+            egui::Button::new(sized_text("Credits", None)),
+        )) {
             *menu = Menu::Credits;
         };
-        if ui
-            .add_sized(
-                [200.0, 100.0],
-                egui::Button::new(sized_text("Options", None)),
-            )
-            .clicked()
-        {
+        if nav.clicked(ui.add_sized(
+            [200.0, 100.0],
+            egui::Button::new(sized_text("Options", None)),
+        )) {
             *menu = Menu::Options;
         }
-        if ui
-            .add_sized([200.0, 100.0], egui::Button::new(sized_text("Exit", None)))
-            .clicked()
-        {
+        if nav.clicked(ui.add_sized([200.0, 100.0], egui::Button::new(sized_text("Exit", None)))) {
             exit.send(AppExit);
         }
     });
@@ -156,9 +350,14 @@ fn menu_options(
     music_volume: &mut ResMut<MusicVolume>,
     effects_volume: &mut ResMut<EffectsVolume>,
     show_tutorials: &mut ResMut<ShowTutorials>,
+    gen_profile: &mut ResMut<SelectedGenProfile>,
+    render_distance: &mut ResMut<RenderDistance>,
+    bindings: &mut Bindings,
+    rebinding: &mut Rebinding,
     ev_save: &mut EventWriter<SaveEvent>,
+    nav: &mut MenuNav,
 ) {
-    if ui.button(sized_text("⬅", None)).clicked() {
+    if nav.clicked(ui.button(sized_text("⬅", None))) || nav.back {
         *menu = Menu::Main;
     }
     ui.vertical(|ui| {
@@ -167,32 +366,128 @@ fn menu_options(
             &mut music_volume.0,
             &mut effects_volume.0,
             &mut show_tutorials.0,
+            &mut render_distance.0,
             ev_save,
-        )
+            nav,
+        );
+
+        // Only offered here since changing it rebuilds the world
+        let mut selected = gen_profile.0.clone();
+        let response = ComboBox::from_label("World Generation")
+            .selected_text(selected.as_str())
+            .show_ui(ui, |ui| {
+                for name in GEN_PRESETS {
+                    ui.selectable_value(&mut selected, name.to_string(), name);
+                }
+            })
+            .response;
+        let nudge = nav.nudged(&response);
+        if nudge != 0 {
+            let current = GEN_PRESETS.iter().position(|name| *name == selected);
+            let next = (current.unwrap_or(0) as i32 + nudge).clamp(0, GEN_PRESETS.len() as i32 - 1);
+            selected = GEN_PRESETS[next as usize].to_string();
+        }
+        if selected != gen_profile.0 {
+            gen_profile.0 = selected;
+            ev_save.send(SaveEvent);
+        }
+
+        ui.separator();
+        key_bindings(ui, bindings, rebinding, ev_save, nav);
     });
 }
 
+fn key_bindings(
+    ui: &mut Ui,
+    bindings: &mut Bindings,
+    rebinding: &mut Rebinding,
+    ev_save: &mut EventWriter<SaveEvent>,
+    nav: &mut MenuNav,
+) {
+    ui.label("Click on a key to change it, or right-click to remove it");
+    egui::Grid::new("key_bindings").show(ui, |ui| {
+        let actions = Action::ALL
+            .into_iter()
+            .filter(|action| cfg!(build = "debug") || !action.debug_only());
+        for action in actions {
+            ui.label(action.name());
+            for slot in 0..KEYS_PER_ACTION {
+                let waiting = rebinding.waiting == Some((action, slot));
+                let text = match bindings.key(action, slot) {
+                    _ if waiting => "Press a key…".to_string(),
+                    Some(key) => key_name(key),
+                    None => "–".to_string(),
+                };
+                let response = ui.add_sized([140., 20.], egui::Button::new(text));
+                let cleared = response.secondary_clicked();
+                let clicked = nav.clicked(response) && !rebinding.just_bound;
+                if cleared {
+                    rebinding.waiting = None;
+                    rebinding.message = match bindings.clear(action, slot) {
+                        Ok(()) => {
+                            ev_save.send(SaveEvent);
+                            String::new()
+                        }
+                        Err(e) => e,
+                    };
+                } else if clicked {
+                    // Clicking again gives up on changing it
+                    rebinding.waiting = match waiting {
+                        true => None,
+                        false => Some((action, slot)),
+                    };
+                }
+            }
+            ui.end_row();
+        }
+    });
+    if !rebinding.message.is_empty() {
+        ui.label(rebinding.message.as_str());
+    }
+}
+
 fn settings_components(
     ui: &mut Ui,
     music_volume: &mut f32,
     effects_volume: &mut f32,
     show_tutorials: &mut bool,
+    render_distance: &mut i32,
     ev_save: &mut EventWriter<SaveEvent>,
+    nav: &mut MenuNav,
 ) {
     let old_music_volume = *music_volume;
-    ui.add(egui::Slider::new(music_volume, 0.0..=100.0).text("Music Volume"));
+    let response = ui.add(egui::Slider::new(music_volume, 0.0..=100.0).text("Music Volume"));
+    *music_volume = (*music_volume + VOLUME_STEP * nav.nudged(&response) as f32).clamp(0., 100.);
     if &old_music_volume != music_volume {
         ev_save.send(SaveEvent);
     }
 
     let old_effects_volume = *effects_volume;
-    ui.add(egui::Slider::new(effects_volume, 0.0..=100.0).text("Sound Effects Volume"));
+    let response =
+        ui.add(egui::Slider::new(effects_volume, 0.0..=100.0).text("Sound Effects Volume"));
+    *effects_volume =
+        (*effects_volume + VOLUME_STEP * nav.nudged(&response) as f32).clamp(0., 100.);
     if &old_effects_volume != effects_volume {
         ev_save.send(SaveEvent);
     }
 
+    // The chunk manager loads or unloads chunks to match
+    let old_render_distance = *render_distance;
+    let response = ui.add(
+        egui::Slider::new(render_distance, MIN_RENDER_DISTANCE..=MAX_RENDER_DISTANCE)
+            .text("Render Distance"),
+    );
+    *render_distance =
+        (*render_distance + nav.nudged(&response)).clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+    if &old_render_distance != render_distance {
+        ev_save.send(SaveEvent);
+    }
+
     let old_show_tutorials = *show_tutorials;
-    ui.checkbox(show_tutorials, "Show Tutorials");
+    let response = ui.checkbox(show_tutorials, "Show Tutorials");
+    if nav.activated(&response) {
+        *show_tutorials = !*show_tutorials;
+    }
     if &old_show_tutorials != show_tutorials {
         ev_save.send(SaveEvent);
     }
@@ -205,9 +500,16 @@ fn pause_menu(
     mut music_volume: ResMut<MusicVolume>,
     mut effects_volume: ResMut<EffectsVolume>,
     mut show_tutorials: ResMut<ShowTutorials>,
+    mut render_distance: ResMut<RenderDistance>,
     mut ev_save: EventWriter<SaveEvent>,
+    mut ev_pause: EventWriter<PauseEvent>,
+    mut nav: ResMut<MenuNav>,
+    bindings: Res<Bindings>,
 ) {
     if paused.0 {
+        if nav.back {
+            ev_pause.send(PauseEvent);
+        }
         match show_tutorial.0 {
             None => {
                 Window::new("Paused").show(egui_context.ctx_mut(), |mut ui| {
@@ -216,7 +518,9 @@ fn pause_menu(
                         &mut music_volume.0,
                         &mut effects_volume.0,
                         &mut show_tutorials.0,
+                        &mut render_distance.0,
                         &mut ev_save,
+                        &mut nav,
                     );
                 });
             }
@@ -228,21 +532,21 @@ fn pause_menu(
                     Tutorial::Minion => "Flock",
                 };
                 let content = match tutorial {
-                    Tutorial::Player => "Control your player with the direction keys or “W”, “A”, “S” and “D”.\n\nFind crates with trapped sheep in and free them to combine into a bigger flock.\n\nTravel as far as you can.\n\nPause/Resume with “Esc” or the gear icon.",
-                    Tutorial::Cage =>"Open a cage by standing near it and pressing “E” or by getting a vaporiser blow it open.",
-                    Tutorial::Launcher=>"The Launcher will periodically fire vaporisers at you. When vaporisers turn blue, they are getting ready to go off.\n Try to find a way to clear a way with them when your path is blocked.",
-                    Tutorial::Minion => "Your flock follows you as the leader. Click on a follower to make it the leader. You can also press “Q” to quick-switch"
+                    Tutorial::Player => format!("Control your player with {}, {}, {} and {} or a gamepad’s left stick, or click on the ground to walk there. Jump up a level with {} or Y on a gamepad, as long as your sheep isn’t out of puff.\n\nFind crates with trapped sheep in and free them to combine into a bigger flock.\n\nTravel as far as you can.\n\nPause/Resume with {}, Start or the gear icon.", bindings.describe(Action::MoveUp), bindings.describe(Action::MoveLeft), bindings.describe(Action::MoveDown), bindings.describe(Action::MoveRight), bindings.describe(Action::Jump), bindings.describe(Action::Pause)),
+                    Tutorial::Cage => format!("Open a cage by standing near it and pressing {} or A on a gamepad, or by getting a vaporiser blow it open.", bindings.describe(Action::Interact)),
+                    Tutorial::Launcher => "The Launcher will periodically fire vaporisers at you. When vaporisers turn blue, they are getting ready to go off.\n Try to find a way to clear a way with them when your path is blocked.".to_string(),
+                    Tutorial::Minion => format!("Your flock follows you as the leader. Click on a follower to make it the leader. You can also press {} or X on a gamepad to quick-switch.\n\nUse the Hold, Regroup and Scatter buttons at the top to make the flock stay put, call it back or scatter it, or hold {}, {} or {} and click on the ground. Do it again to call the order off.", bindings.describe(Action::SwitchLeader), bindings.describe(Action::HoldOrder), bindings.describe(Action::RegroupOrder), bindings.describe(Action::ScatterOrder)),
                 };
                 Window::new(format!("Tip: {tip_name}")).show(egui_context.ctx_mut(), |ui| {
-                    ui.label(sized_text(content, Some(20.)))
+                    ui.label(sized_text(content.as_str(), Some(20.)))
                 });
             }
         }
     }
 }
 
-fn menu_credits(ui: &mut Ui, menu: &mut Menu) {
-    if ui.button(sized_text("⬅", None)).clicked() {
+fn menu_credits(ui: &mut Ui, menu: &mut Menu, nav: &mut MenuNav) {
+    if nav.clicked(ui.button(sized_text("⬅", None))) || nav.back {
         *menu = Menu::Main;
     }
     ui.label(sized_text("Attributions", None));
@@ -260,10 +564,14 @@ fn menu_credits(ui: &mut Ui, menu: &mut Menu) {
     ));
 }
 
-fn menu_game_over(ui: &mut Ui, current_score: &Res<CurrentScore>) {
+fn menu_game_over(ui: &mut Ui, current_score: &Res<CurrentScore>, sheep_lost: &Res<SheepLost>) {
     ui.label(sized_text("Game Over", Some(60.)));
     ui.label(sized_text(
         format!("Ewe got a score of: {}", current_score.0).as_str(),
         None,
     ));
+    ui.label(sized_text(
+        format!("Sheep lost along the way: {}", sheep_lost.0).as_str(),
+        None,
+    ));
 }
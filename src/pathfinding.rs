@@ -0,0 +1,125 @@
+//! Path search over tiles, counted from the start of the world so that paths
+//! stay valid when the floating origin moves
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// A tile's position in tiles from the start, as given by `Chunk::to_tile`
+pub type TilePos = (i64, i64);
+
+fn neighbours((x, z): TilePos) -> [TilePos; 4] {
+    [(x + 1, z), (x - 1, z), (x, z + 1), (x, z - 1)]
+}
+
+/// How many steps apart two tiles are
+pub fn distance((x, z): TilePos, (gx, gz): TilePos) -> u64 {
+    x.abs_diff(gx) + z.abs_diff(gz)
+}
+
+/// The shortest route from `start` to `goal`, not including `start`, using
+/// `can_step(from, to)` to tell which moves between neighbouring tiles are
+/// allowed.
+///
+/// At most `max_visits` tiles are looked at. If the goal can't be reached in
+/// that many, the route goes as close to it as it can instead, or is `None`
+/// if nothing closer than `start` was found.
+pub fn find_path(
+    start: TilePos,
+    goal: TilePos,
+    max_visits: usize,
+    can_step: impl Fn(TilePos, TilePos) -> bool,
+) -> Option<Vec<TilePos>> {
+    let mut costs: HashMap<TilePos, u64> = HashMap::from([(start, 0)]);
+    let mut previous: HashMap<TilePos, TilePos> = HashMap::new();
+    let mut queue = BinaryHeap::from([Reverse((distance(start, goal), 0, start))]);
+    let mut closest = (distance(start, goal), start);
+
+    let mut visits = 0;
+    while let Some(Reverse((_, cost, tile))) = queue.pop() {
+        if cost > costs[&tile] {
+            continue;
+        }
+        closest = closest.min((distance(tile, goal), tile));
+        if tile == goal || visits >= max_visits {
+            break;
+        }
+        visits += 1;
+        for next in neighbours(tile) {
+            if costs.get(&next).map_or(true, |&known| cost + 1 < known) && can_step(tile, next) {
+                costs.insert(next, cost + 1);
+                previous.insert(next, tile);
+                queue.push(Reverse((cost + 1 + distance(next, goal), cost + 1, next)));
+            }
+        }
+    }
+
+    let (_, end) = closest;
+    if end == start {
+        return None;
+    }
+    let mut route = vec![end];
+    while let Some(&tile) = previous.get(route.last().expect("The route starts non-empty")) {
+        if tile == start {
+            break;
+        }
+        route.push(tile);
+    }
+    route.reverse();
+    Some(route)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN: fn(TilePos, TilePos) -> bool = |_, _| true;
+
+    #[test]
+    fn open_ground_takes_the_shortest_route() {
+        let route = find_path((0, 0), (3, -2), 100, OPEN).unwrap();
+        assert_eq!(route.len(), 5);
+        assert_eq!(route.last(), Some(&(3, -2)));
+        for (a, b) in std::iter::once((0, 0)).chain(route.clone()).zip(route) {
+            assert_eq!(distance(a, b), 1);
+        }
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        // A wall along x = 1 with a gap at z = 3
+        let wall = |_: TilePos, (x, z): TilePos| x != 1 || z == 3;
+        let route = find_path((0, 0), (2, 0), 100, wall).unwrap();
+        assert!(route.contains(&(1, 3)));
+        assert_eq!(route.last(), Some(&(2, 0)));
+        assert_eq!(route.len(), 8);
+    }
+
+    #[test]
+    fn steps_can_depend_on_where_they_come_from() {
+        // Tiles with x > 0 are a plateau that can only be left, not climbed
+        let height = |(x, _): TilePos| (x > 0) as i32;
+        let climb = |from: TilePos, to: TilePos| height(to) <= height(from);
+        assert_eq!(find_path((0, 0), (2, 0), 100, climb), None);
+        assert_eq!(find_path((2, 0), (-1, 0), 100, climb).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn unreachable_goals_get_as_close_as_they_can() {
+        let fence = |_: TilePos, (x, _): TilePos| x < 3;
+        let route = find_path((0, 0), (6, 1), 1000, fence).unwrap();
+        assert_eq!(route.last(), Some(&(2, 1)));
+    }
+
+    #[test]
+    fn searches_are_cut_short() {
+        let route = find_path((0, 0), (500, 0), 20, OPEN).unwrap();
+        assert!(route.len() <= 21);
+        assert!(route.last().unwrap().0 > 0);
+    }
+
+    #[test]
+    fn nothing_to_do_when_boxed_in() {
+        assert_eq!(find_path((0, 0), (5, 5), 100, |_, _| false), None);
+        assert_eq!(find_path((0, 0), (0, 0), 100, OPEN), None);
+    }
+}
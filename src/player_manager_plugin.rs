@@ -1,15 +1,31 @@
-use crate::chunk_manager_plugin::{Chunk, ChunkChangeEvent, FBLOCK_SIZE, RENDER_DISTANCE};
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::{
+    ChunkChangeEvent, RenderDistance, TerrainChangeEvent, TileEditedEvent, WorldGrid, FBLOCK_SIZE,
+};
+use crate::controls::{Action, ActionInput};
+use crate::flocking::{steer, Boid, FlockOrder, FlockWeights, SpatialGrid};
 use crate::follow_plugin::FollowTarget;
 use crate::follow_plugin::FollowTargetMoveEvent;
+use crate::hazard_plugin::Wading;
+use crate::health::Health;
+use crate::health_plugin::SheepDeathEvent;
 use crate::item_plugin::{EquipGiveEvent, EquipTakeEvent, ItemType};
+use crate::jumping::JumpBudget;
 use crate::menu_plugin::Menu;
+use crate::pathfinding::{distance, find_path, TilePos};
 use crate::settings_plugin::SaveEvent;
-use crate::share::OldLoc;
+use crate::share::{ChunkTerrain, OldLoc};
+use crate::streaming::StreamingPolicy;
 use bevy::app::Plugin;
 use bevy::prelude::*;
+use bevy_egui::EguiContext;
 use bevy_mod_picking::events::PickingEvent;
+use bevy_mod_picking::PickingCamera;
+use bevy_rapier3d::prelude::Damping;
 use bevy_rapier3d::prelude::ExternalForce;
 use bevy_rapier3d::prelude::Velocity;
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RapierContext};
+use std::collections::VecDeque;
 
 pub struct PlayerManagerPlugin;
 
@@ -19,6 +35,51 @@ pub struct Player;
 #[derive(Component)]
 pub struct Minion;
 
+/// How hard the player pushes their sheep along
+const MOVE_FORCE: f32 = 5.;
+
+/// How many levels a sheep can get up onto by jumping
+pub const STEP_HEIGHT: usize = 1;
+
+/// How fast a sheep leaves the ground when it jumps, which is enough to get up
+/// one level
+const JUMP_SPEED: f32 = 5.5;
+
+/// The ball swept down from the middle of a sheep to look for the ground,
+/// which is a little narrower than the sheep so that walls don't count
+const GROUND_PROBE_RADIUS: f32 = 0.45;
+
+/// How far below a sheep the ground can be for it to still be standing on it
+const GROUND_REACH: f32 = 0.15;
+
+/// How many tiles the player can move before minions look for a new path
+const REPLAN_DISTANCE: u64 = 3;
+
+/// How many tiles a single path search can look at
+const MAX_PATH_VISITS: usize = 1500;
+
+/// How many paths can be found each frame
+const PATHS_PER_FRAME: usize = 4;
+
+/// The route that a minion is taking to catch up with the player, in tiles
+/// from the start of the world
+#[derive(Component, Default)]
+pub struct MinionPath {
+    /// Where the player was when the path was found
+    goal: Option<TilePos>,
+    waypoints: VecDeque<TilePos>,
+    /// Whether the terrain has changed since the path was found
+    stale: bool,
+}
+
+/// Where the player has clicked for the leader to go, and the route there in
+/// tiles from the start of the world
+#[derive(Default)]
+pub struct LeaderPath {
+    goal: Option<TilePos>,
+    waypoints: VecDeque<TilePos>,
+}
+
 #[derive(Component)]
 pub struct Inventory {
     pub hand: Option<ItemType>,
@@ -26,12 +87,20 @@ pub struct Inventory {
 
 impl Plugin for PlayerManagerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(player_movement)
-            .add_system(player_location_manager)
+        app.init_resource::<FlockWeights>()
+            .init_resource::<FlockOrder>()
+            .init_resource::<LeaderPath>()
+            .add_system(click_orders.label("click_orders"))
+            .add_system(plan_leader_path.label("leader_path").after("click_orders"))
+            .add_system(player_movement.after("leader_path"))
+            .add_system(sheep_jumping.after("leader_path").after("minion_paths"))
+            .add_system(player_location_manager.label("player_location"))
             .add_system(equip_player)
-            .add_system(minion_ai)
+            .add_system(plan_minion_paths.label("minion_paths"))
+            .add_system(minion_ai.after("minion_paths"))
             .add_system(change_controlled)
-            .add_system(on_player_death)
+            .add_system(on_player_death.after("damage"))
+            .add_system(biome_effects)
             .add_system(minion_location_manager);
     }
 }
@@ -40,6 +109,7 @@ fn minion_location_manager(
     mut minions: Query<(Entity, &mut Transform), (&Minion, Without<Player>)>,
     mut commands: Commands,
     player: Query<&Transform, &Player>,
+    render_distance: Res<RenderDistance>,
 ) {
     for (_, mut transform) in minions.iter_mut() {
         if transform.translation.y < 0.5 {
@@ -48,7 +118,8 @@ fn minion_location_manager(
     }
     for orig in player.iter() {
         for (ent, trans) in minions.iter() {
-            if orig.translation.distance(trans.translation) > FBLOCK_SIZE * (RENDER_DISTANCE as f32)
+            if orig.translation.distance(trans.translation)
+                > FBLOCK_SIZE * (render_distance.0 as f32)
             {
                 commands.entity(ent).despawn_recursive();
             }
@@ -81,18 +152,15 @@ fn player_location_manager(
             let (cx, cz) = (transform.translation.x, transform.translation.z);
             let (ox, oz) = (oldloc.0, oldloc.1);
             let (dx, dz) = (
-                (((cx / FBLOCK_SIZE).floor()) - ((ox / FBLOCK_SIZE).floor())) as isize,
-                (((cz / FBLOCK_SIZE).floor()) - ((oz / FBLOCK_SIZE).floor())) as isize,
+                (((cx / FBLOCK_SIZE).floor()) - ((ox / FBLOCK_SIZE).floor())) as i32,
+                (((cz / FBLOCK_SIZE).floor()) - ((oz / FBLOCK_SIZE).floor())) as i32,
             );
 
             if dx != 0 || dz != 0 {
-                let newchunk = Chunk(
-                    chunk.0.saturating_add_signed(dx),
-                    chunk.1.saturating_add_signed(dz),
-                );
+                let newchunk = chunk.offset(dx, dz);
                 ev_chunk_change.send(ChunkChangeEvent {
-                    oldchunk: chunk.clone(),
-                    newchunk: newchunk.clone(),
+                    oldchunk: *chunk,
+                    newchunk,
                 });
                 *chunk = newchunk;
             }
@@ -101,9 +169,12 @@ fn player_location_manager(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut head_positions: Query<(&mut ExternalForce, &mut Velocity, &Transform), &Player>,
+    controls: ActionInput,
+    mut leader_path: ResMut<LeaderPath>,
+    current_chunk: Res<Chunk>,
+    mut head_positions: Query<(&mut ExternalForce, &Transform), &Player>,
     mut ev_equip: EventWriter<EquipTakeEvent>,
     mut ev_pick: EventWriter<PickingEvent>,
     rapier_config: Res<bevy_rapier3d::plugin::RapierConfiguration>,
@@ -111,38 +182,34 @@ fn player_movement(
     menu: Res<Menu>,
 ) {
     if rapier_config.physics_pipeline_active && *menu == Menu::Game {
-        for (mut ef, mut vel, transform) in head_positions.iter_mut() {
-            if keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A) {
-                ef.force.x = -5.0;
-            } else if keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D) {
-                ef.force.x = 5.0;
-            } else {
-                ef.force.x = 0.0;
-            }
-
-            if keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::S) {
-                ef.force.z = 5.0;
-            } else if keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::W) {
-                ef.force.z = -5.0;
-            } else {
-                ef.force.z = 0.0;
-            }
-
-            #[cfg(build = "debug")]
-            {
-                if keyboard_input.pressed(KeyCode::Space) {
-                    vel.linvel.y = 5.0;
+        // The stick pushes harder the further it is tilted
+        let movement = controls.movement();
+        if movement != Vec2::ZERO {
+            // Steering by hand calls off going where was clicked
+            *leader_path = LeaderPath::default();
+        }
+        for (mut ef, transform) in head_positions.iter_mut() {
+            match leader_path.waypoints.front() {
+                Some(&tile) => {
+                    let to = Chunk::tile_to_world(&current_chunk, tile) - transform.translation;
+                    let to = Vec2::new(to.x, to.z).normalize_or_zero();
+                    ef.force.x = to.x * MOVE_FORCE;
+                    ef.force.z = to.y * MOVE_FORCE;
+                }
+                None => {
+                    ef.force.x = movement.x * MOVE_FORCE;
+                    ef.force.z = -movement.y * MOVE_FORCE;
                 }
             }
 
-            if keyboard_input.just_pressed(KeyCode::E) {
+            if controls.just_pressed(Action::Interact) {
                 ev_equip.send(EquipTakeEvent {
                     pos: transform.translation,
                     reach: 3.,
                 });
             }
 
-            if keyboard_input.just_pressed(KeyCode::Q) {
+            if controls.just_pressed(Action::SwitchLeader) {
                 for ent in minions.iter().next() {
                     ev_pick.send(PickingEvent::Clicked(ent));
                 }
@@ -151,6 +218,91 @@ fn player_movement(
     }
 }
 
+/// Jump when the player says to, or when the next tile on a sheep's path is
+/// higher up
+#[allow(clippy::too_many_arguments)]
+fn sheep_jumping(
+    controls: ActionInput,
+    rapier_context: Res<RapierContext>,
+    rapier_config: Res<bevy_rapier3d::plugin::RapierConfiguration>,
+    time: Res<Time>,
+    leader_path: Res<LeaderPath>,
+    world_grid: Res<WorldGrid>,
+    current_chunk: Res<Chunk>,
+    menu: Res<Menu>,
+    mut sheep: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Velocity,
+            &mut JumpBudget,
+            Option<&Player>,
+            Option<&MinionPath>,
+        ),
+        Or<(&Player, &Minion)>,
+    >,
+) {
+    if !rapier_config.physics_pipeline_active || *menu != Menu::Game {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    let probe = Collider::ball(GROUND_PROBE_RADIUS);
+    let height = |(x, z): TilePos| {
+        let (chunk, x, z) = Chunk::from_tile(x, z);
+        world_grid.tile(&chunk, x, z).map(|tile| tile.height)
+    };
+
+    for (ent, transform, mut velocity, mut budget, player, path) in sheep.iter_mut() {
+        // Hazard sensors aren't solid, so they don't count as ground
+        let filter = QueryFilter::default()
+            .exclude_collider(ent)
+            .exclude_sensors();
+        let grounded = rapier_context
+            .cast_shape(
+                transform.translation,
+                Quat::IDENTITY,
+                -Vec3::Y,
+                &probe,
+                GROUND_REACH,
+                filter,
+            )
+            .is_some();
+        budget.update(grounded, now, time.delta_seconds());
+
+        let next = match player {
+            Some(_) => leader_path.waypoints.front(),
+            None => path.and_then(|path| path.waypoints.front()),
+        };
+        let here = Chunk::tile_from_world(&current_chunk, transform.translation);
+        let climb = next.map_or(
+            false,
+            |&next| matches!((height(here), height(next)), (Some(here), Some(next)) if next > here),
+        );
+        let jump = climb || (player.is_some() && controls.just_pressed(Action::Jump));
+        if jump && budget.try_jump(now) {
+            velocity.linvel.y = JUMP_SPEED;
+        }
+    }
+}
+
+/// Slow down or speed up sheep depending on the biome they are in, unless
+/// they are in water
+fn biome_effects(
+    mut sheep: Query<(&Transform, &mut Damping, &Wading), Or<(&Player, &Minion)>>,
+    world_grid: Res<WorldGrid>,
+    chunk: Res<Chunk>,
+) {
+    for (transform, mut damping, _) in sheep.iter_mut().filter(|(_, _, wading)| !wading.in_water())
+    {
+        if let Some(tile) = world_grid.tile_at(&chunk, transform.translation) {
+            let linear_damping = tile.biome.linear_damping();
+            if damping.linear_damping != linear_damping {
+                damping.linear_damping = linear_damping;
+            }
+        }
+    }
+}
+
 fn equip_player(mut ev_equip: EventReader<EquipGiveEvent>) {
     for EquipGiveEvent { item } in ev_equip.iter().last() {
         match item {
@@ -159,57 +311,300 @@ fn equip_player(mut ev_equip: EventReader<EquipGiveEvent>) {
     }
 }
 
-fn minion_ai(
-    player_positions: Query<&Transform, &Player>,
-    mut minions: Query<(&Transform, &mut ExternalForce), &Minion>,
+/// Whether a sheep can walk from a tile onto the one next to it, with tiles in
+/// chunks that aren't loaded counted as blocked
+fn walkable<'a>(
+    world_grid: &'a WorldGrid,
+    streaming: &'a StreamingPolicy,
+) -> impl Fn(TilePos, TilePos) -> bool + 'a {
+    let tile = move |(x, z): TilePos| {
+        let (chunk, x, z) = Chunk::from_tile(x, z);
+        match streaming.is_loaded(&chunk) {
+            true => world_grid.tile(&chunk, x, z),
+            false => None,
+        }
+    };
+    move |from: TilePos, to: TilePos| match (tile(from), tile(to)) {
+        (Some(from), Some(to)) => {
+            to.height <= from.height + STEP_HEIGHT
+                && !to.copse
+                && !to.kind.blocks_path()
+                && to.item != Some(ItemType::Cage)
+        }
+        _ => false,
+    }
+}
+
+/// Send the leader towards the terrain that was clicked on, or give the flock
+/// an order if one of the order keys is held down.
+///
+/// Orders can also be given with the buttons in the HUD, which doesn't need a
+/// keyboard.
+#[allow(clippy::too_many_arguments)]
+fn click_orders(
+    mouse_input: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    controls: ActionInput,
+    mut egui_context: ResMut<EguiContext>,
+    cameras: Query<&PickingCamera>,
+    terrain: Query<(), With<ChunkTerrain>>,
+    mut leader_path: ResMut<LeaderPath>,
+    mut order: ResMut<FlockOrder>,
+    current_chunk: Res<Chunk>,
+    rapier_config: Res<bevy_rapier3d::plugin::RapierConfiguration>,
+    menu: Res<Menu>,
 ) {
-    for player_pos in player_positions.iter().last() {
-        for (minion_pos, mut minion_force) in minions.iter_mut() {
-            if minion_pos.translation.distance(player_pos.translation) > 3. {
-                let direction = player_pos.translation - minion_pos.translation;
-                if direction.length() > 0.1 {
-                    minion_force.force = direction.normalize() * 2.5;
-                }
+    let clicked =
+        mouse_input.just_pressed(MouseButton::Left) || touches.iter_just_pressed().next().is_some();
+    if !clicked
+        || !rapier_config.physics_pipeline_active
+        || *menu != Menu::Game
+        || egui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    // Clicks on sheep are for changing the leader instead
+    let hit = cameras
+        .iter()
+        .filter_map(|camera| camera.intersect_top())
+        .find(|(ent, _)| terrain.get(*ent).is_ok());
+    let intersection = match hit {
+        Some((_, intersection)) => intersection,
+        None => return,
+    };
+
+    let given = [
+        (Action::HoldOrder, FlockOrder::Hold),
+        (Action::RegroupOrder, FlockOrder::Regroup),
+        (Action::ScatterOrder, FlockOrder::Scatter),
+    ]
+    .into_iter()
+    .find(|(action, _)| controls.pressed(*action));
+    match given {
+        Some((_, given)) => order.toggle(given),
+        None => {
+            // Step just inside the surface so that clicking the side of a
+            // raised tile goes to that tile
+            let pos = intersection.position() - intersection.normal() * 0.1;
+            leader_path.goal = Some(Chunk::tile_from_world(&current_chunk, pos));
+            leader_path.waypoints.clear();
+        }
+    }
+}
+
+fn plan_leader_path(
+    mut ev_terrain_change: EventReader<TerrainChangeEvent>,
+    mut ev_tile_edited: EventReader<TileEditedEvent>,
+    player: Query<&Transform, &Player>,
+    mut leader_path: ResMut<LeaderPath>,
+    world_grid: Res<WorldGrid>,
+    streaming: Res<StreamingPolicy>,
+    current_chunk: Res<Chunk>,
+) {
+    let terrain_changed = ev_terrain_change.iter().count() + ev_tile_edited.iter().count() > 0;
+    let (goal, leader) = match (leader_path.goal, player.iter().last()) {
+        (Some(goal), Some(transform)) => (goal, transform.translation),
+        _ => return,
+    };
+    let start = Chunk::tile_from_world(&current_chunk, leader);
+    if start == goal {
+        *leader_path = LeaderPath::default();
+        return;
+    }
+
+    if let Some(reached) = leader_path.waypoints.iter().position(|&tile| tile == start) {
+        leader_path.waypoints.drain(..=reached);
+    }
+    let strayed = matches!(leader_path.waypoints.front(), Some(&next) if distance(start, next) > 1);
+    if terrain_changed || strayed || leader_path.waypoints.is_empty() {
+        match find_path(
+            start,
+            goal,
+            MAX_PATH_VISITS,
+            walkable(&world_grid, &streaming),
+        ) {
+            Some(route) => leader_path.waypoints = route.into(),
+            // Nowhere closer to go
+            None => *leader_path = LeaderPath::default(),
+        }
+    }
+}
+
+/// Find routes for minions that have fallen behind around anything that they
+/// can't walk over
+#[allow(clippy::too_many_arguments)]
+fn plan_minion_paths(
+    mut commands: Commands,
+    mut ev_terrain_change: EventReader<TerrainChangeEvent>,
+    mut ev_tile_edited: EventReader<TileEditedEvent>,
+    player: Query<&Transform, &Player>,
+    mut minions: Query<(Entity, &Transform, Option<&mut MinionPath>), (&Minion, Without<Player>)>,
+    world_grid: Res<WorldGrid>,
+    streaming: Res<StreamingPolicy>,
+    current_chunk: Res<Chunk>,
+    weights: Res<FlockWeights>,
+    order: Res<FlockOrder>,
+) {
+    // A bomb or an edit could have blocked a path or opened up a shorter one
+    let terrain_changed = ev_terrain_change.iter().count() + ev_tile_edited.iter().count() > 0;
+    let leader = match player.iter().last() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+    let goal = Chunk::tile_from_world(&current_chunk, leader);
+    // Only following and regrouping use paths to the leader
+    let heading_for_leader = matches!(*order, FlockOrder::Follow | FlockOrder::Regroup);
+    let can_step = walkable(&world_grid, &streaming);
+
+    let mut budget = PATHS_PER_FRAME;
+    for (ent, transform, path) in minions.iter_mut() {
+        let mut path = match path {
+            Some(path) => path,
+            None => {
+                commands.entity(ent).insert(MinionPath::default());
+                continue;
             }
+        };
+        path.stale |= terrain_changed;
+        if !heading_for_leader || transform.translation.distance(leader) <= weights.leader_radius {
+            path.waypoints.clear();
+            continue;
+        }
+
+        let start = Chunk::tile_from_world(&current_chunk, transform.translation);
+        if let Some(reached) = path.waypoints.iter().position(|&tile| tile == start) {
+            path.waypoints.drain(..=reached);
+        }
+        let strayed = matches!(path.waypoints.front(), Some(&next) if distance(start, next) > 1);
+        let goal_moved = path
+            .goal
+            .map_or(true, |old| distance(old, goal) > REPLAN_DISTANCE);
+        if budget > 0 && (path.stale || strayed || goal_moved) {
+            budget -= 1;
+            path.goal = Some(goal);
+            path.stale = false;
+            path.waypoints = find_path(start, goal, MAX_PATH_VISITS, &can_step)
+                .unwrap_or_default()
+                .into();
         }
     }
 }
 
+/// Steer minions as a flock that follows the player, or does what it has
+/// been ordered to
+fn minion_ai(
+    player: Query<(&Transform, &Velocity), &Player>,
+    mut minions: Query<
+        (
+            &Transform,
+            &Velocity,
+            &mut ExternalForce,
+            Option<&MinionPath>,
+        ),
+        (&Minion, Without<Player>),
+    >,
+    weights: Res<FlockWeights>,
+    mut order: ResMut<FlockOrder>,
+    current_chunk: Res<Chunk>,
+) {
+    let leader = player.iter().last();
+
+    // The leader goes first so that the flock keeps out of its way too
+    let boids: Vec<Boid> = leader
+        .into_iter()
+        .chain(
+            minions
+                .iter()
+                .map(|(transform, velocity, _, _)| (transform, velocity)),
+        )
+        .map(|(transform, velocity)| Boid {
+            pos: transform.translation,
+            vel: velocity.linvel,
+        })
+        .collect();
+    let grid = SpatialGrid::new(&boids, weights.neighbour_radius);
+    let first_minion = leader.is_some() as usize;
+    let leader_pos = leader.map(|(transform, _)| transform.translation);
+
+    for (i, (_, _, mut force, path)) in minions.iter_mut().enumerate() {
+        let waypoint = path
+            .and_then(|path| path.waypoints.front())
+            .map(|&tile| Chunk::tile_to_world(&current_chunk, tile));
+        force.force = steer(
+            first_minion + i,
+            &boids,
+            &grid,
+            leader_pos,
+            waypoint,
+            *order,
+            &weights,
+        );
+    }
+
+    // Go back to following once everyone has caught up
+    if *order == FlockOrder::Regroup {
+        let regrouped = leader_pos.map_or(true, |leader| {
+            boids[first_minion..]
+                .iter()
+                .all(|boid| boid.pos.distance(leader) <= weights.leader_radius)
+        });
+        if regrouped {
+            *order = FlockOrder::Follow;
+        }
+    }
+}
+
+/// Hand over to the nearest follower when the leader dies, or end the game if
+/// there is no one left to take over
 fn on_player_death(
+    mut ev_death: EventReader<SheepDeathEvent>,
     player: Query<&OldLoc, &Player>,
-    minions: Query<Entity, &Minion>,
+    minions: Query<(Entity, &Transform, &Health), (&Minion, Without<Player>)>,
     mut ev_selection: EventWriter<PickingEvent>,
     mut ev_save: EventWriter<SaveEvent>,
-    mut last_loc: Local<Option<OldLoc>>,
     mut commands: Commands,
     mut menu: ResMut<Menu>,
 ) {
-    if player.is_empty() && last_loc.is_some() {
-        if let Some(ent) = minions.iter().last() {
-            commands
-                .entity(ent)
-                .insert(last_loc.expect("Last Loc should be set from the start"));
+    let death = match ev_death.iter().find(|death| death.was_leader) {
+        Some(death) => death,
+        None => return,
+    };
+    // Sheep that died at the same time aren't despawned until the end of the
+    // frame
+    let successor = minions
+        .iter()
+        .filter(|(_, _, health)| health.hp() > 0)
+        .min_by(|(_, a, _), (_, b, _)| {
+            let a = a.translation.distance(death.pos);
+            a.total_cmp(&b.translation.distance(death.pos))
+        });
+    match successor {
+        Some((ent, _, _)) => {
+            // Keep track of chunks from where the leader was
+            if let Ok(loc) = player.get(death.entity) {
+                commands.entity(ent).insert(*loc);
+            }
             ev_selection.send(PickingEvent::Clicked(ent));
-        } else {
+        }
+        None => {
             ev_save.send(SaveEvent);
             *menu = Menu::GameOver;
         }
-    } else {
-        for loc in player.iter() {
-            *last_loc = Some(*loc);
-        }
     }
 }
 
 fn change_controlled(
     mut ev_selection: EventReader<PickingEvent>,
-    mut player: Query<(Entity, &Transform), &Player>,
+    mut player: Query<(Entity, &Transform, &Health), &Player>,
+    minions: Query<&Health, (&Minion, Without<Player>)>,
     mut commands: Commands,
 ) {
+    // Only living sheep in the flock can become the leader, not anything else
+    // that can be clicked on
     for ev in ev_selection
         .iter()
         .filter(|v| match v {
-            PickingEvent::Clicked(_) => true,
+            PickingEvent::Clicked(ent) => minions.get(*ent).map_or(false, |health| health.hp() > 0),
             _ => false,
         })
         .last()
@@ -217,7 +612,9 @@ fn change_controlled(
         match ev {
             PickingEvent::Clicked(ent) => {
                 let mut oldloc = None;
-                for (ent, trans) in player.iter_mut() {
+                // A leader that has just died is despawned at the end of the
+                // frame, so it can't be made a minion
+                for (ent, trans, _) in player.iter_mut().filter(|(_, _, health)| health.hp() > 0) {
                     commands
                         .entity(ent)
                         .remove::<Player>()
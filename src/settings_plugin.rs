@@ -1,3 +1,5 @@
+use crate::chunk_manager_plugin::{RenderDistance, SelectedGenProfile};
+use crate::controls::Bindings;
 use crate::game_plugin::HighScores;
 use crate::sound_plugin::{EffectsVolume, MusicVolume};
 use crate::tutorial_plugin::ShowTutorials;
@@ -19,6 +21,8 @@ enum SettingType {
     String(String),
     Bool(bool),
     Pair(isize, isize),
+    Int(isize),
+    Keys(Bindings),
 }
 
 impl Plugin for SettingsPlugin {
@@ -34,6 +38,9 @@ fn setup_config(
     mut rhigh_scores: ResMut<HighScores>,
     mut rshow_tutorials: ResMut<ShowTutorials>,
     mut reffects_volume: ResMut<EffectsVolume>,
+    mut rgen_profile: ResMut<SelectedGenProfile>,
+    mut rrender_distance: ResMut<RenderDistance>,
+    mut rbindings: ResMut<Bindings>,
 ) {
     if let Ok(data) = read_to_string("settings.json") {
         let settings: Result<HashMap<String, SettingType>, _> = serde_json::from_str(&data);
@@ -50,6 +57,17 @@ fn setup_config(
             if let Some(SettingType::Pair(ls, hs)) = settings.get("high_scores") {
                 *rhigh_scores = HighScores(*ls, *hs)
             }
+            if let Some(SettingType::String(gen_profile)) = settings.get("gen_profile") {
+                rgen_profile.0 = gen_profile.clone();
+            }
+            if let Some(SettingType::Int(render_distance)) = settings.get("render_distance") {
+                rrender_distance.0 = *render_distance as i32;
+            }
+            if let Some(SettingType::Keys(bindings)) = settings.get("key_bindings") {
+                if let Err(err) = rbindings.load(bindings) {
+                    println!("Ignoring saved key bindings: {}", err)
+                }
+            }
         }
     }
 }
@@ -60,16 +78,25 @@ fn save_config(
     reffects_volume: Res<EffectsVolume>,
     rshow_tutorials: Res<ShowTutorials>,
     rhigh_scores: Res<HighScores>,
+    rgen_profile: Res<SelectedGenProfile>,
+    rrender_distance: Res<RenderDistance>,
+    rbindings: Res<Bindings>,
 ) {
-    let mut settings = HashMap::new();
-    settings.insert("music_volume", SettingType::Float(rmusic_volume.0));
-    settings.insert("effects_volume", SettingType::Float(reffects_volume.0));
-    settings.insert("show_tutorials", SettingType::Bool(rshow_tutorials.0));
-    settings.insert(
-        "high_scores",
-        SettingType::Pair(rhigh_scores.0, rhigh_scores.1),
-    );
     for _ in ev_save.iter().last() {
+        let mut settings = HashMap::new();
+        settings.insert("music_volume", SettingType::Float(rmusic_volume.0));
+        settings.insert("effects_volume", SettingType::Float(reffects_volume.0));
+        settings.insert("show_tutorials", SettingType::Bool(rshow_tutorials.0));
+        settings.insert(
+            "high_scores",
+            SettingType::Pair(rhigh_scores.0, rhigh_scores.1),
+        );
+        settings.insert("gen_profile", SettingType::String(rgen_profile.0.clone()));
+        settings.insert(
+            "render_distance",
+            SettingType::Int(rrender_distance.0 as isize),
+        );
+        settings.insert("key_bindings", SettingType::Keys(rbindings.clone()));
         let new_file = File::create("settings.json");
         if let Ok(mut output) = new_file {
             let stringified = serde_json::to_string(&settings);
@@ -1,14 +1,35 @@
 //! A module for shared resources that don't have a special home yet
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::chunk::Chunk;
 use crate::item_plugin::ItemType;
 
 #[derive(Component, Copy, Clone)]
 pub struct OldLoc(pub f32, pub f32);
 
 #[derive(Component, Hash, Eq, PartialEq, Clone, Debug)]
-pub struct Terrain(pub usize, pub usize);
+pub struct Terrain(pub Chunk);
+
+/// The part of a tile that an entity was spawned for or that has changed
+#[derive(Component, Copy, Clone, Debug)]
+pub enum TilePart {
+    /// The ground itself, which has been lowered by some number of levels
+    Block {
+        lowered: usize,
+    },
+    Copse,
+    Item,
+}
+
+/// The position of an entity's tile within its chunk
+#[derive(Component, Copy, Clone, Debug)]
+pub struct TileRef {
+    pub x: usize,
+    pub z: usize,
+    pub part: TilePart,
+}
 
 #[derive(Component)]
 pub struct DynamicPos;
@@ -19,12 +40,17 @@ pub struct Camera;
 #[derive(Component)]
 pub struct Indestructible;
 
+/// A merged mesh drawing part of a chunk's terrain
+#[derive(Component)]
+pub struct ChunkTerrain;
+
 #[derive(Debug, Copy, Clone)]
 pub struct TileSettings {
     pub height: usize,
     pub copse: bool,
     pub kind: TileType,
     pub item: Option<ItemType>,
+    pub biome: Biome,
 }
 impl Default for TileSettings {
     fn default() -> Self {
@@ -33,19 +59,121 @@ impl Default for TileSettings {
             copse: false,
             kind: TileType::Base,
             item: None,
+            biome: Biome::Meadow,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Base,
     B,
+    Gravel,
+    Mud,
+    Reeds,
+    Snow,
+    Ice,
+    Sand,
+    Sandstone,
+    Water,
+    Lava,
+    Spikes,
+}
+
+impl TileType {
+    pub const ALL: [TileType; 12] = [
+        TileType::Base,
+        TileType::B,
+        TileType::Gravel,
+        TileType::Mud,
+        TileType::Reeds,
+        TileType::Snow,
+        TileType::Ice,
+        TileType::Sand,
+        TileType::Sandstone,
+        TileType::Water,
+        TileType::Lava,
+        TileType::Spikes,
+    ];
+
+    /// Whether sheep are affected by touching this kind of tile
+    pub fn is_hazard(&self) -> bool {
+        matches!(self, TileType::Water | TileType::Lava | TileType::Spikes)
+    }
+
+    /// Whether a route across a chunk has to go around this kind of tile,
+    /// since crossing it would hurt
+    pub fn blocks_path(&self) -> bool {
+        matches!(self, TileType::Lava | TileType::Spikes)
+    }
 }
 
 pub fn type_to_colour(s: &TileType) -> Color {
     match s {
         TileType::Base => Color::rgb(0.1, 0.5, 0.1),
         TileType::B => Color::rgb(0.3, 0.3, 0.3),
+        TileType::Gravel => Color::rgb(0.45, 0.42, 0.38),
+        TileType::Mud => Color::rgb(0.3, 0.22, 0.12),
+        TileType::Reeds => Color::rgb(0.35, 0.45, 0.2),
+        TileType::Snow => Color::rgb(0.95, 0.95, 0.97),
+        TileType::Ice => Color::rgb(0.7, 0.85, 0.95),
+        TileType::Sand => Color::rgb(0.85, 0.75, 0.45),
+        TileType::Sandstone => Color::rgb(0.75, 0.55, 0.35),
+        TileType::Water => Color::rgb(0.15, 0.35, 0.8),
+        TileType::Lava => Color::rgb(0.95, 0.3, 0.05),
+        TileType::Spikes => Color::rgb(0.5, 0.45, 0.5),
+    }
+}
+
+/// A region of the world with its own look and feel
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Biome {
+    Meadow,
+    RockyHighland,
+    Marsh,
+    Snowfield,
+    Sand,
+}
+
+impl Biome {
+    /// The tile type for ground at floor level
+    pub fn ground(&self) -> TileType {
+        match self {
+            Biome::Meadow => TileType::Base,
+            Biome::RockyHighland => TileType::Gravel,
+            Biome::Marsh => TileType::Mud,
+            Biome::Snowfield => TileType::Snow,
+            Biome::Sand => TileType::Sand,
+        }
+    }
+
+    /// The tile type for raised ground
+    pub fn raised(&self) -> TileType {
+        match self {
+            Biome::Meadow | Biome::RockyHighland => TileType::B,
+            Biome::Marsh => TileType::Reeds,
+            Biome::Snowfield => TileType::Ice,
+            Biome::Sand => TileType::Sandstone,
+        }
+    }
+
+    /// The tile type for hazards on the floor
+    pub fn hazard(&self) -> TileType {
+        match self {
+            Biome::Meadow | Biome::Marsh | Biome::Snowfield => TileType::Water,
+            Biome::RockyHighland => TileType::Lava,
+            Biome::Sand => TileType::Spikes,
+        }
+    }
+
+    /// The linear damping of a sheep standing in this biome, so marsh is slow
+    /// going and snow is slippery
+    pub fn linear_damping(&self) -> f32 {
+        match self {
+            Biome::Meadow | Biome::RockyHighland => 1.,
+            Biome::Marsh => 3.,
+            Biome::Snowfield => 0.2,
+            Biome::Sand => 1.6,
+        }
     }
 }
@@ -0,0 +1,203 @@
+//! Decides which chunks are loaded around the player
+
+use crate::chunk::Chunk;
+use std::collections::{HashSet, VecDeque};
+
+/// How many seconds of loads and unloads the metrics cover
+const METRICS_WINDOW: f64 = 60.;
+
+/// Which chunks should be loaded around the player.
+///
+/// Chunks load once they are within `load_radius` of the player but are only
+/// unloaded once they are more than `unload_radius` away, so walking back and
+/// forth over a chunk boundary doesn't keep spawning and despawning them.
+/// Distances are counted in whole chunks along the furthest axis.
+pub struct StreamingPolicy {
+    pub load_radius: i32,
+    pub unload_radius: i32,
+    loaded: HashSet<Chunk>,
+    loads: VecDeque<f64>,
+    unloads: VecDeque<f64>,
+}
+
+/// The chunks that a move has brought into and out of range
+#[derive(Default, Debug)]
+pub struct StreamingChanges {
+    pub load: Vec<Chunk>,
+    pub unload: Vec<Chunk>,
+}
+
+impl StreamingPolicy {
+    /// A policy that keeps `hysteresis` more chunks around than it loads
+    pub fn new(load_radius: i32, hysteresis: i32) -> Self {
+        StreamingPolicy {
+            load_radius,
+            unload_radius: load_radius + hysteresis.max(0),
+            loaded: HashSet::new(),
+            loads: VecDeque::new(),
+            unloads: VecDeque::new(),
+        }
+    }
+
+    /// Change how far out chunks are loaded, which takes effect on the next
+    /// update
+    pub fn set_radius(&mut self, load_radius: i32, hysteresis: i32) {
+        self.load_radius = load_radius;
+        self.unload_radius = load_radius + hysteresis.max(0);
+    }
+
+    pub fn is_loaded(&self, chunk: &Chunk) -> bool {
+        self.loaded.contains(chunk)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loaded.is_empty()
+    }
+
+    /// Work out what to load and unload with the player in `centre`, at
+    /// `now` seconds into the game.
+    ///
+    /// `lane_width` limits how many chunks either side of the player along z
+    /// are loaded.
+    pub fn update(&mut self, centre: &Chunk, lane_width: i32, now: f64) -> StreamingChanges {
+        let mut changes = StreamingChanges::default();
+
+        let unload_radius = self.unload_radius as i64;
+        self.loaded.retain(|chunk| {
+            let (dx, dz) = centre.delta(chunk);
+            let keep = dx.abs().max(dz.abs()) <= unload_radius;
+            if !keep {
+                changes.unload.push(*chunk);
+            }
+            keep
+        });
+
+        let (rx, rz) = (self.load_radius, self.load_radius.min(lane_width));
+        for dx in -rx..=rx {
+            for dz in -rz..=rz {
+                let chunk = centre.offset(dx, dz);
+                if self.loaded.insert(chunk) {
+                    changes.load.push(chunk);
+                }
+            }
+        }
+
+        self.loads.extend(changes.load.iter().map(|_| now));
+        self.unloads.extend(changes.unload.iter().map(|_| now));
+        self.forget_before(now - METRICS_WINDOW);
+        changes
+    }
+
+    /// Forget every loaded chunk without counting them as unloaded, such as
+    /// when the world is being regenerated
+    pub fn reset(&mut self) -> bool {
+        let was_loaded = !self.loaded.is_empty();
+        self.loaded.clear();
+        was_loaded
+    }
+
+    /// How many chunks have been loaded in the last minute
+    pub fn loads_per_minute(&mut self, now: f64) -> usize {
+        self.forget_before(now - METRICS_WINDOW);
+        self.loads.len()
+    }
+
+    /// How many chunks have been unloaded in the last minute
+    pub fn unloads_per_minute(&mut self, now: f64) -> usize {
+        self.forget_before(now - METRICS_WINDOW);
+        self.unloads.len()
+    }
+
+    fn forget_before(&mut self, time: f64) {
+        for times in [&mut self.loads, &mut self.unloads] {
+            while matches!(times.front(), Some(t) if *t < time) {
+                times.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_a_square_around_the_player() {
+        let mut policy = StreamingPolicy::new(1, 1);
+        let changes = policy.update(&Chunk::ORIGIN, 1, 0.);
+        assert_eq!(changes.load.len(), 9);
+        assert!(changes.unload.is_empty());
+        assert!(policy.is_loaded(&Chunk(-1, 1)));
+        assert!(!policy.is_loaded(&Chunk(2, 0)));
+    }
+
+    #[test]
+    fn lanes_only_load_along_x() {
+        let mut policy = StreamingPolicy::new(1, 1);
+        let changes = policy.update(&Chunk::ORIGIN, 0, 0.);
+        assert_eq!(changes.load.len(), 3);
+        assert!(changes.load.iter().all(|chunk| chunk.1 == 0));
+    }
+
+    #[test]
+    fn crossing_back_and_forth_does_not_thrash() {
+        let mut policy = StreamingPolicy::new(1, 1);
+        policy.update(&Chunk::ORIGIN, 0, 0.);
+        let forward = policy.update(&Chunk(1, 0), 0, 1.);
+        assert_eq!(forward.load, vec![Chunk(2, 0)]);
+        assert!(forward.unload.is_empty());
+
+        for step in 0..10 {
+            let chunk = if step % 2 == 0 {
+                Chunk::ORIGIN
+            } else {
+                Chunk(1, 0)
+            };
+            let changes = policy.update(&chunk, 0, 2. + step as f64);
+            assert!(changes.load.is_empty() && changes.unload.is_empty());
+        }
+    }
+
+    #[test]
+    fn unloads_past_the_unload_radius() {
+        let mut policy = StreamingPolicy::new(1, 1);
+        policy.update(&Chunk::ORIGIN, 0, 0.);
+        let changes = policy.update(&Chunk(3, 0), 0, 1.);
+        let mut unloaded = changes.unload;
+        unloaded.sort_by_key(|chunk| chunk.0);
+        assert_eq!(unloaded, vec![Chunk(-1, 0), Chunk(0, 0)]);
+        assert!(policy.is_loaded(&Chunk(1, 0)));
+    }
+
+    #[test]
+    fn shrinking_the_radius_unloads_on_the_next_update() {
+        let mut policy = StreamingPolicy::new(3, 1);
+        policy.update(&Chunk::ORIGIN, 0, 0.);
+        policy.set_radius(1, 1);
+        let changes = policy.update(&Chunk::ORIGIN, 0, 1.);
+        assert!(changes.load.is_empty());
+        assert_eq!(changes.unload.len(), 2);
+        assert!(!policy.is_loaded(&Chunk(3, 0)));
+        assert!(policy.is_loaded(&Chunk(2, 0)));
+    }
+
+    #[test]
+    fn metrics_cover_the_last_minute() {
+        let mut policy = StreamingPolicy::new(1, 0);
+        policy.update(&Chunk::ORIGIN, 0, 0.);
+        policy.update(&Chunk(5, 0), 0, 30.);
+        assert_eq!(policy.loads_per_minute(30.), 6);
+        assert_eq!(policy.unloads_per_minute(30.), 3);
+        assert_eq!(policy.loads_per_minute(75.), 3);
+        assert_eq!(policy.unloads_per_minute(91.), 0);
+    }
+
+    #[test]
+    fn reset_does_not_count_as_unloading() {
+        let mut policy = StreamingPolicy::new(1, 1);
+        policy.update(&Chunk::ORIGIN, 0, 0.);
+        assert!(policy.reset());
+        assert!(!policy.reset());
+        assert_eq!(policy.unloads_per_minute(1.), 0);
+    }
+}
@@ -1,9 +1,14 @@
-use crate::asset_plugin::{Objects, TriggerLoopAnimEvent};
-use crate::chunk_manager_plugin::Chunk;
+use crate::asset_plugin::{Objects, TilePalette, TriggerLoopAnimEvent};
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::Block;
 use crate::follow_plugin::*;
+use crate::hazard_plugin::{Hazard, Wading};
+use crate::health::{Health, SHEEP_HEALTH};
 use crate::item_plugin::{Item, ItemType};
+use crate::jumping::JumpBudget;
 use crate::player_manager_plugin::Inventory;
 use crate::share::*;
+use crate::terrain_mesh::{build_chunk_collider, build_chunk_meshes};
 use bevy::prelude::*;
 use bevy_mod_picking::*;
 use bevy_rapier3d::prelude::*;
@@ -49,6 +54,9 @@ pub fn make_main_player(
         })
         .insert(DynamicPos)
         .insert(Inventory { hand: None })
+        .insert(Health::new(SHEEP_HEALTH))
+        .insert(Wading::default())
+        .insert(JumpBudget::default())
         .insert_bundle(PickableBundle::default())
         .insert_bundle(PickableBundle::default())
         .insert_bundle(PbrBundle {
@@ -66,57 +74,79 @@ pub fn make_main_player(
     ent
 }
 
+/// Spawn the merged terrain meshes and collider for a chunk with its corner
+/// at `offset`
+pub fn make_chunk_terrain(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    palette: &TilePalette,
+    block: &Block,
+    chunk: &Chunk,
+    offset: Vec3,
+) {
+    if let Some(collider) = build_chunk_collider(block) {
+        commands
+            .spawn_bundle(TransformBundle::from(Transform::from_translation(offset)))
+            .insert(collider)
+            .insert(ChunkTerrain)
+            .insert(Terrain(*chunk))
+            .insert(Indestructible)
+            .insert(DynamicPos);
+    }
+    for (kind, mesh) in build_chunk_meshes(block) {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(mesh),
+                material: palette.0[&kind].clone_weak(),
+                transform: Transform::from_translation(offset),
+                ..default()
+            })
+            .insert(PickableMesh::default())
+            .insert(ChunkTerrain)
+            .insert(Terrain(*chunk))
+            .insert(Indestructible)
+            .insert(DynamicPos);
+    }
+}
+
+/// Spawn everything on a tile other than the terrain itself, which is drawn
+/// by [`make_chunk_terrain`]
 pub fn entities_for_tile(
     commands: &mut Commands,
-    materials: &mut Assets<StandardMaterial>,
     tile_settings: &TileSettings,
     chunk: &Chunk,
+    (x, z): (usize, usize),
     pos: (Option<f32>, Option<f32>, Option<f32>),
     objects: &Res<Objects>,
 ) {
     let TileSettings {
         kind,
+        biome: _,
         copse,
         height,
         item,
     } = tile_settings;
-    let ent = commands
-        .spawn_bundle(PbrBundle {
-            mesh: objects.0[&"cube".to_string()].clone_weak().typed(),
-            material: materials.add(type_to_colour(&kind).into()),
-            transform: Transform::from_xyz(
+    if kind.is_hazard() {
+        // A sensor just above the tile for sheep to touch
+        commands
+            .spawn_bundle(TransformBundle::from(Transform::from_xyz(
                 pos.0.unwrap_or(0.),
-                pos.1.unwrap_or(*height as f32),
+                *height as f32 + 1.,
                 pos.2.unwrap_or(0.),
-            ),
-            ..default()
-        })
-        .insert(Terrain(chunk.0, chunk.1))
-        .insert(DynamicPos)
-        .id();
-
-    if *height == 0 {
-        commands.entity(ent).insert(Indestructible);
-    }
-
-    if *height > 0 {
-        commands.entity(ent).insert(Collider::cuboid(0.5, 0.5, 0.5));
-        commands
-            .spawn_bundle(PbrBundle {
-                mesh: objects.0[&"cube".to_string()].clone_weak().typed(),
-                material: materials.add(type_to_colour(&TileType::Base).into()),
-                transform: Transform::from_xyz(
-                    pos.0.unwrap_or(0.),
-                    pos.1.unwrap_or(0.),
-                    pos.2.unwrap_or(0.),
-                ),
-                ..default()
+            )))
+            .insert(Collider::cuboid(0.45, 0.5, 0.45))
+            .insert(Sensor)
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Hazard(*kind))
+            .insert(Terrain(*chunk))
+            .insert(TileRef {
+                x,
+                z,
+                part: TilePart::Block { lowered: 0 },
             })
-            .insert(DynamicPos)
             .insert(Indestructible)
-            .insert(Terrain(chunk.0, chunk.1));
+            .insert(DynamicPos);
     }
-
     if *copse {
         commands
             .spawn_bundle(SceneBundle {
@@ -129,7 +159,12 @@ pub fn entities_for_tile(
                 ..default()
             })
             .insert(Collider::round_cylinder(4., 0.2, 0.2))
-            .insert(Terrain(chunk.0, chunk.1))
+            .insert(Terrain(*chunk))
+            .insert(TileRef {
+                x,
+                z,
+                part: TilePart::Copse,
+            })
             .insert(DynamicPos);
     }
     match *item {
@@ -154,7 +189,12 @@ pub fn entities_for_tile(
                             _ => 0.,
                         },
                 ))
-                .insert(Terrain(chunk.0, chunk.1))
+                .insert(Terrain(*chunk))
+                .insert(TileRef {
+                    x,
+                    z,
+                    part: TilePart::Item,
+                })
                 .insert(DynamicPos);
         }
         _ => {}
@@ -0,0 +1,159 @@
+//! Builds the merged meshes and colliders for a chunk's terrain
+
+use crate::chunk_manager_plugin::{Block, BLOCK_SIZE};
+use crate::share::TileType;
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy_rapier3d::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct MeshData {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Add a quad facing along `u.cross(v)` with `u` and `v` as half extents
+    fn quad(&mut self, centre: Vec3, u: Vec3, v: Vec3) {
+        let start = self.positions.len() as u32;
+        let normal = u.cross(v).normalize();
+        for (corner, uv) in [
+            (centre - u - v, [0., 0.]),
+            (centre + u - v, [1., 0.]),
+            (centre + u + v, [1., 1.]),
+            (centre - u + v, [0., 1.]),
+        ] {
+            self.positions.push(corner.to_array());
+            self.normals.push(normal.to_array());
+            self.uvs.push(uv);
+        }
+        self.indices
+            .extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+/// Build one mesh per tile type for a chunk, relative to the chunk's corner.
+///
+/// Each tile is a column of unit cubes from the floor up to its height, so
+/// only the top and the sides that can be seen past neighbouring columns are
+/// included.
+pub fn build_chunk_meshes(block: &Block) -> Vec<(TileType, Mesh)> {
+    let mut data: HashMap<TileType, MeshData> = HashMap::new();
+
+    // Tiles outside of the chunk are treated as being below the floor so that
+    // the edges of the chunk are closed off
+    let top = |x: isize, z: isize| -> f32 {
+        if x < 0 || z < 0 || x >= BLOCK_SIZE as isize || z >= BLOCK_SIZE as isize {
+            -0.5
+        } else {
+            block[x as usize][z as usize].height as f32 + 0.5
+        }
+    };
+
+    for (x, col) in block.iter().enumerate() {
+        for (z, tile) in col.iter().enumerate() {
+            let mesh = data.entry(tile.kind).or_default();
+            let (fx, fz) = (x as f32, z as f32);
+            let (ix, iz) = (x as isize, z as isize);
+            let height = top(ix, iz);
+
+            mesh.quad(Vec3::new(fx, height, fz), Vec3::Z * 0.5, Vec3::X * 0.5);
+
+            for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let neighbour = top(ix + dx, iz + dz);
+                if neighbour >= height {
+                    continue;
+                }
+                let half_height = (height - neighbour) / 2.;
+                let centre = Vec3::new(
+                    fx + (dx as f32) * 0.5,
+                    neighbour + half_height,
+                    fz + (dz as f32) * 0.5,
+                );
+                let (u, v) = match (dx, dz) {
+                    (1, _) => (Vec3::Y * half_height, Vec3::Z * 0.5),
+                    (-1, _) => (Vec3::Z * 0.5, Vec3::Y * half_height),
+                    (_, 1) => (Vec3::X * 0.5, Vec3::Y * half_height),
+                    _ => (Vec3::Y * half_height, Vec3::X * 0.5),
+                };
+                mesh.quad(centre, u, v);
+            }
+        }
+    }
+
+    data.into_iter()
+        .map(|(kind, mesh)| (kind, mesh.into_mesh()))
+        .collect()
+}
+
+/// Build a single compound collider for the raised tiles of a chunk, relative
+/// to the chunk's corner.
+///
+/// Neighbouring tiles of the same height are merged into larger boxes so
+/// that there are fewer shapes and fewer seams for sheep to catch on.
+pub fn build_chunk_collider(block: &Block) -> Option<Collider> {
+    let mut covered = [[false; BLOCK_SIZE]; BLOCK_SIZE];
+    let mut shapes = Vec::new();
+
+    for x in 0..BLOCK_SIZE {
+        for z in 0..BLOCK_SIZE {
+            let height = block[x][z].height;
+            if height == 0 || covered[x][z] {
+                continue;
+            }
+            let matches = |x: usize, z: usize| !covered[x][z] && block[x][z].height == height;
+
+            // Grow along z first and then along x for as long as every tile
+            // in the run still matches
+            let mut z_end = z + 1;
+            while z_end < BLOCK_SIZE && matches(x, z_end) {
+                z_end += 1;
+            }
+            let mut x_end = x + 1;
+            while x_end < BLOCK_SIZE && (z..z_end).all(|z| matches(x_end, z)) {
+                x_end += 1;
+            }
+            for row in covered.iter_mut().take(x_end).skip(x) {
+                for tile in row.iter_mut().take(z_end).skip(z) {
+                    *tile = true;
+                }
+            }
+
+            // Solid from the top of the floor collider up to the tile's top
+            let half_extents = Vec3::new(
+                (x_end - x) as f32 / 2.,
+                height as f32 / 2.,
+                (z_end - z) as f32 / 2.,
+            );
+            let centre = Vec3::new(
+                x as f32 - 0.5 + half_extents.x,
+                0.5 + half_extents.y,
+                z as f32 - 0.5 + half_extents.z,
+            );
+            shapes.push((
+                centre,
+                Quat::IDENTITY,
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            ));
+        }
+    }
+
+    if shapes.is_empty() {
+        None
+    } else {
+        Some(Collider::compound(shapes))
+    }
+}
@@ -0,0 +1,878 @@
+//! Procedural generation of chunks.
+//!
+//! A chunk only depends on its position, the seed, the profile and the
+//! prefabs, so that generation can run on the async compute pool or without
+//! a window at all.
+
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::{Block, BLOCK_SIZE, MAX_ELEV};
+use crate::item_plugin::ItemType;
+use crate::share::{Biome, TileSettings, TileType};
+use noise::{NoiseFn, Perlin, Seedable};
+use rand::Rng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// The highest a cage can sit and still be opened from the floor next to it
+const MAX_CAGE_HEIGHT: usize = 2;
+
+/// How many tiles in from the edge of a chunk a prefab blends into the
+/// terrain around it
+const PREFAB_BLEND: usize = 3;
+
+/// The characters that exported prefabs use for their tiles, leaving out `~`
+/// since it keeps the generated tile
+const PREFAB_CHARS: &str =
+    ".#abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!$%&*+-=?@^_|";
+
+/// The presets that ship in `assets/worldgen`
+pub const GEN_PRESETS: [&str; 3] = ["default", "rugged", "gentle"];
+
+/// The prefabs that ship in `assets/prefabs`
+pub const PREFABS: [&str; 2] = ["sheep_pen", "launcher_gauntlet"];
+
+/// Tuning for the world generator, loaded from a `.gen.json` file.
+///
+/// `BLOCK_SIZE` isn't part of this since the floating origin, chunk
+/// streaming and colliders all rely on it being fixed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct GenProfile {
+    /// Tile coordinates are divided by this before sampling noise, so larger
+    /// values give broader features
+    pub noise_scale: f64,
+    /// Elevation noise above this raises a tile
+    pub elevation_threshold: f64,
+    /// The height of a tile at the very top of the elevation noise in the
+    /// first zone, which can't be more than `MAX_ELEV`
+    pub max_elevation: usize,
+    /// Tree noise above this grows a copse
+    pub tree_threshold: f64,
+    /// Item noise above this places a launcher while the chunk has some left
+    pub launcher_threshold: f64,
+    pub launchers_per_chunk: usize,
+    /// Item noise above this places the chunk's cage
+    pub cage_threshold: f64,
+    /// How much broader biomes are than the features of the terrain
+    pub biome_scale: f64,
+    /// How many chunks from the start before every biome can appear
+    pub biome_spread: f64,
+    /// How many chunks from the start each difficulty zone covers
+    pub zone_length: f64,
+    /// The hardest zone, after which things stop getting harder
+    pub max_zone: usize,
+    /// Extra launchers per chunk for each zone, rounded down
+    pub launchers_per_zone: f64,
+    /// How much each zone lowers the elevation threshold by
+    pub elevation_per_zone: f64,
+    /// How much each zone raises the tallest tiles by, rounded down, until
+    /// they reach `MAX_ELEV`
+    pub max_elevation_per_zone: f64,
+    /// How much each zone raises the cage threshold by
+    pub cage_threshold_per_zone: f64,
+    /// How many seconds each zone takes off a bomb's fuse
+    pub fuse_per_zone: f32,
+    /// Roughly one in this many chunks is a prefab, or none if zero
+    pub prefab_interval: usize,
+    /// Hazard noise above this turns an empty tile on the floor into the
+    /// biome's hazard
+    pub hazard_threshold: f64,
+    /// How much each zone lowers the hazard threshold by
+    pub hazard_threshold_per_zone: f64,
+}
+
+impl Default for GenProfile {
+    fn default() -> Self {
+        GenProfile {
+            noise_scale: 10.,
+            elevation_threshold: 0.125,
+            max_elevation: 3,
+            tree_threshold: 0.7,
+            launcher_threshold: 0.,
+            launchers_per_chunk: 2,
+            cage_threshold: 0.9,
+            biome_scale: 8.,
+            biome_spread: 10.,
+            zone_length: 5.,
+            max_zone: 5,
+            launchers_per_zone: 0.5,
+            elevation_per_zone: 0.03,
+            max_elevation_per_zone: 0.4,
+            cage_threshold_per_zone: 0.01,
+            fuse_per_zone: 1.,
+            prefab_interval: 6,
+            hazard_threshold: 0.5,
+            hazard_threshold_per_zone: 0.03,
+        }
+    }
+}
+
+/// What a prefab puts on one of its tiles, where anything left out is
+/// taken from the generated terrain
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct PrefabCell {
+    pub height: Option<usize>,
+    pub kind: Option<TileType>,
+    pub copse: Option<bool>,
+    pub item: Option<ItemType>,
+}
+
+/// A hand-authored chunk, loaded from a `.prefab.json` file.
+///
+/// `rows` is a top down map of the chunk with one row for each z and one
+/// character for each x, and `legend` says what each character means.
+/// Characters that aren't in the legend leave the generated tile as it is.
+///
+/// A prefab with a chunk in `at` is a fixed level that always goes in that
+/// chunk as it is, rather than being mixed in at random.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Prefab {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<Chunk>,
+    pub rows: Vec<String>,
+    pub legend: HashMap<String, PrefabCell>,
+}
+
+impl Prefab {
+    /// Capture every tile of a chunk, such as one made in the level editor
+    #[cfg_attr(not(build = "debug"), allow(dead_code))]
+    pub fn from_block(name: &str, block: &Block, at: Option<Chunk>) -> Result<Prefab, String> {
+        let mut cells: Vec<PrefabCell> = Vec::new();
+        let mut rows = vec![String::new(); BLOCK_SIZE];
+        for (x, col) in block.iter().enumerate() {
+            for (z, tile) in col.iter().enumerate() {
+                let cell = PrefabCell {
+                    height: Some(tile.height),
+                    kind: Some(tile.kind),
+                    copse: Some(tile.copse),
+                    item: tile.item,
+                };
+                let index = match cells.iter().position(|c| *c == cell) {
+                    Some(index) => index,
+                    None => {
+                        cells.push(cell);
+                        cells.len() - 1
+                    }
+                };
+                let c = PREFAB_CHARS
+                    .chars()
+                    .nth(index)
+                    .ok_or("The chunk has too many different tiles to export")?;
+                // Rows go along x, so each column adds a character to them all
+                debug_assert_eq!(rows[z].chars().count(), x);
+                rows[z].push(c);
+            }
+        }
+        Ok(Prefab {
+            name: name.to_string(),
+            at,
+            rows,
+            legend: cells
+                .into_iter()
+                .zip(PREFAB_CHARS.chars())
+                .map(|(cell, c)| (c.to_string(), cell))
+                .collect(),
+        })
+    }
+
+    fn cell(&self, x: usize, z: usize) -> Option<&PrefabCell> {
+        let c = self.rows.get(z)?.chars().nth(x)?;
+        self.legend.get(&c.to_string())
+    }
+
+    /// Lay the prefab over a generated chunk, easing its heights into the
+    /// generated ones towards the edges
+    fn stamp(&self, block: &mut Block) {
+        for (x, col) in block.iter_mut().enumerate() {
+            for (z, tile) in col.iter_mut().enumerate() {
+                // Items are placed by the prefab alone so that the chunk
+                // doesn't end up with extra cages or launchers
+                tile.item = None;
+
+                let cell = match self.cell(x, z) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+                // Fixed levels are kept exactly as they were made
+                let edge = x.min(z).min(BLOCK_SIZE - 1 - x).min(BLOCK_SIZE - 1 - z);
+                let weight = match self.at {
+                    Some(_) => 1.,
+                    None => ((edge + 1) as f64 / (PREFAB_BLEND + 1) as f64).min(1.),
+                };
+
+                if let Some(height) = cell.height {
+                    let blended =
+                        tile.height as f64 + (height as f64 - tile.height as f64) * weight;
+                    tile.height = (blended.round() as usize).min(MAX_ELEV);
+                }
+                tile.kind = match cell.kind {
+                    Some(kind) if weight >= 0.5 => kind,
+                    _ if tile.height > 0 => tile.biome.raised(),
+                    _ => tile.biome.ground(),
+                };
+                if let Some(copse) = cell.copse {
+                    tile.copse = copse;
+                }
+                tile.item = cell.item;
+            }
+        }
+    }
+}
+
+/// Pick the prefab, if any, that goes in a chunk
+fn choose_prefab<'a>(
+    chunk: &Chunk,
+    seed: u32,
+    profile: &GenProfile,
+    prefabs: &'a [Prefab],
+) -> Option<&'a Prefab> {
+    if let Some(fixed) = prefabs.iter().find(|prefab| prefab.at == Some(*chunk)) {
+        return Some(fixed);
+    }
+    let prefabs: Vec<&Prefab> = prefabs
+        .iter()
+        .filter(|prefab| prefab.at.is_none())
+        .collect();
+
+    // Never at the start so that the player always begins in open country
+    if profile.prefab_interval == 0 || prefabs.is_empty() || *chunk == Chunk::ORIGIN {
+        return None;
+    }
+    let (chunk_x, chunk_z) = noise_coords(chunk);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(
+        (chunk_x * (2_u64.pow(32)) + chunk_z)
+            .wrapping_add(seed as u64)
+            .rotate_left(17),
+    );
+    if rng.gen_range(0..profile.prefab_interval) == 0 {
+        Some(prefabs[rng.gen_range(0..prefabs.len())])
+    } else {
+        None
+    }
+}
+
+/// How long a bomb takes to go off in the first zone
+pub const BASE_FUSE: f32 = 10.;
+
+/// The shortest that a bomb's fuse can get
+pub const MIN_FUSE: f32 = 4.;
+
+/// Where the start chunk sits in the noise. Chunks used to be counted from
+/// here, so keeping it means that seeds still give the same worlds.
+const NOISE_ORIGIN: i64 = (u32::MAX / 2) as i64;
+
+/// The position of a chunk in the noise
+fn noise_coords(chunk: &Chunk) -> (u64, u64) {
+    (
+        (NOISE_ORIGIN + chunk.0 as i64) as u64,
+        (NOISE_ORIGIN + chunk.1 as i64) as u64,
+    )
+}
+
+impl GenProfile {
+    /// The difficulty zone that a chunk is in, starting from zero
+    pub fn zone(&self, chunk: &Chunk) -> usize {
+        ((chunk.distance_from_origin() / self.zone_length.max(1.)) as usize).min(self.max_zone)
+    }
+
+    /// The most launchers that a chunk in a zone can have
+    pub fn launchers(&self, zone: usize) -> usize {
+        self.launchers_per_chunk + (self.launchers_per_zone * zone as f64) as usize
+    }
+
+    /// Reject values that generation can't honour, so that a mistake in a
+    /// profile shows up when it's loaded
+    pub fn check(&self) -> Result<(), String> {
+        if self.max_elevation > MAX_ELEV {
+            return Err(format!(
+                "max_elevation is {} but tiles can't be taller than {}",
+                self.max_elevation, MAX_ELEV
+            ));
+        }
+        Ok(())
+    }
+
+    /// How long the fuse is on bombs fired from a zone
+    pub fn fuse(&self, zone: usize) -> f32 {
+        (BASE_FUSE - self.fuse_per_zone * zone as f32).max(MIN_FUSE)
+    }
+}
+
+/// How a biome shifts the generator's thresholds, where positive values make
+/// something rarer
+struct BiomeOdds {
+    elevation: f64,
+    trees: f64,
+    launchers: f64,
+    cages: f64,
+    hazards: f64,
+}
+
+impl BiomeOdds {
+    fn of(biome: Biome) -> Self {
+        match biome {
+            Biome::Meadow => BiomeOdds {
+                elevation: 0.,
+                trees: 0.,
+                launchers: 0.,
+                cages: 0.,
+                hazards: 0.1,
+            },
+            Biome::RockyHighland => BiomeOdds {
+                elevation: -0.1,
+                trees: 0.15,
+                launchers: -0.1,
+                cages: 0.,
+                hazards: 0.,
+            },
+            Biome::Marsh => BiomeOdds {
+                elevation: 0.2,
+                trees: -0.1,
+                launchers: 0.1,
+                cages: -0.05,
+                hazards: -0.2,
+            },
+            Biome::Snowfield => BiomeOdds {
+                elevation: 0.05,
+                trees: 0.1,
+                launchers: 0.,
+                cages: 0.05,
+                hazards: 0.1,
+            },
+            Biome::Sand => BiomeOdds {
+                elevation: 0.1,
+                trees: 0.25,
+                launchers: -0.05,
+                cages: 0.,
+                hazards: 0.,
+            },
+        }
+    }
+}
+
+/// Map biome noise to a biome, with meadow in the middle of the range
+fn pick_biome(noise: f64) -> Biome {
+    if noise < -0.6 {
+        Biome::Sand
+    } else if noise < -0.2 {
+        Biome::Marsh
+    } else if noise < 0.2 {
+        Biome::Meadow
+    } else if noise < 0.6 {
+        Biome::RockyHighland
+    } else {
+        Biome::Snowfield
+    }
+}
+
+pub fn propogate_block(
+    chunk: &Chunk,
+    seed: Option<u32>,
+    profile: &GenProfile,
+    prefabs: &[Prefab],
+) -> Block {
+    let seed = seed.unwrap_or(0);
+    let perlin_elev = Perlin::new().set_seed(seed);
+    let perlin_trees = Perlin::new().set_seed(seed.wrapping_add(100));
+    let perlin_items = Perlin::new().set_seed(seed.wrapping_add(200));
+    let perlin_biome = Perlin::new().set_seed(seed.wrapping_add(300));
+    let perlin_hazards = Perlin::new().set_seed(seed.wrapping_add(400));
+
+    // Keep to meadow around the start and open up to every biome further out
+    let variety = (chunk.distance_from_origin() / profile.biome_spread).min(1.);
+
+    // Chunks further out are harder
+    let zone_index = profile.zone(chunk);
+    let zone = zone_index as f64;
+    let elevation_threshold = profile.elevation_threshold - profile.elevation_per_zone * zone;
+    let max_elevation = profile.max_elevation + (profile.max_elevation_per_zone * zone) as usize;
+    let cage_threshold = profile.cage_threshold + profile.cage_threshold_per_zone * zone;
+    let hazard_threshold = profile.hazard_threshold - profile.hazard_threshold_per_zone * zone;
+
+    let (chunk_x, chunk_z) = noise_coords(chunk);
+
+    let mut block = [[TileSettings::default(); BLOCK_SIZE]; BLOCK_SIZE];
+    let mut has_cage = false;
+    let mut launcher_count = profile.launchers(zone_index);
+
+    // Visit blocks in a pseudo random order
+    let mut rng = rand::rngs::StdRng::seed_from_u64(
+        (chunk_x * (2_u64.pow(32)) + chunk_z).wrapping_add(seed as u64),
+    );
+
+    let mut rows: Vec<usize> = (0..BLOCK_SIZE).collect();
+    while !rows.is_empty() {
+        let x = rows.swap_remove(rng.gen::<usize>() % rows.len());
+        let mut cols: Vec<usize> = (0..BLOCK_SIZE).collect();
+        while !cols.is_empty() {
+            let z = cols.swap_remove(rng.gen::<usize>() % cols.len());
+            let perlin_x = (chunk_x as f64 * (BLOCK_SIZE as f64) + x as f64) / profile.noise_scale;
+            let perlin_z = (chunk_z as f64 * (BLOCK_SIZE as f64) + z as f64) / profile.noise_scale;
+            let elev = perlin_elev.get([perlin_x, perlin_z]);
+            let trees = perlin_trees.get([perlin_x, perlin_z]);
+
+            let items = perlin_items.get([perlin_x, perlin_z]);
+            let hazards = perlin_hazards.get([perlin_x, perlin_z]);
+
+            let biome = pick_biome(
+                perlin_biome.get([
+                    perlin_x / profile.biome_scale,
+                    perlin_z / profile.biome_scale,
+                ]) * variety
+                    * 1.5,
+            );
+            let odds = BiomeOdds::of(biome);
+            block[x][z].biome = biome;
+            block[x][z].kind = biome.ground();
+
+            let elevation_threshold = (elevation_threshold + odds.elevation).min(0.99);
+            if elev > elevation_threshold {
+                let norm = (elev - elevation_threshold) / (1.0 - elevation_threshold);
+                block[x][z].kind = biome.raised();
+                block[x][z].height =
+                    (((norm * ((max_elevation.max(1) - 1) as f64)) as usize) + 1).min(MAX_ELEV);
+            }
+
+            if 0 != launcher_count && items > profile.launcher_threshold + odds.launchers {
+                launcher_count -= 1;
+                block[x][z].item = Some(ItemType::Launcher);
+            } else if !has_cage && items > cage_threshold + odds.cages {
+                has_cage = true;
+                block[x][z].item = Some(ItemType::Cage);
+            } else if trees > profile.tree_threshold + odds.trees {
+                block[x][z].copse = true;
+            } else if block[x][z].height == 0 && hazards > hazard_threshold + odds.hazards {
+                block[x][z].kind = biome.hazard();
+            }
+        }
+    }
+    let prefab = choose_prefab(chunk, seed, profile, prefabs);
+    if let Some(prefab) = prefab {
+        prefab.stamp(&mut block);
+    }
+    // Fixed levels are left to whoever made them, so nothing is carved
+    if prefab.map_or(true, |prefab| prefab.at.is_none()) {
+        // The neighbouring chunks work out the same rows, so their routes meet
+        let (entry, exit) = (
+            crossing_row(chunk, seed),
+            crossing_row(&chunk.offset(1, 0), seed),
+        );
+        ensure_traversable(&mut block, entry, exit);
+    }
+    block
+}
+
+/// The row where routes cross from the chunk to the left of `chunk` into it
+fn crossing_row(chunk: &Chunk, seed: u32) -> usize {
+    let (chunk_x, chunk_z) = noise_coords(chunk);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(
+        (chunk_x * (2_u64.pow(32)) + chunk_z)
+            .wrapping_add(seed as u64)
+            .rotate_left(31),
+    );
+    rng.gen_range(0..BLOCK_SIZE)
+}
+
+/// What it would take to walk over a tile, or `None` if it must be left alone
+fn clearing_cost(tile: &TileSettings) -> Option<usize> {
+    match tile.item {
+        Some(ItemType::Cage) => None,
+        _ => Some(tile.height + tile.copse as usize + tile.kind.blocks_path() as usize),
+    }
+}
+
+fn neighbours(x: usize, z: usize) -> impl Iterator<Item = (usize, usize)> {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .into_iter()
+        .map(move |(dx, dz)| (x as isize + dx, z as isize + dz))
+        .filter(|(x, z)| {
+            (0..BLOCK_SIZE as isize).contains(x) && (0..BLOCK_SIZE as isize).contains(z)
+        })
+        .map(|(x, z)| (x as usize, z as usize))
+}
+
+/// The cheapest route to clear from any of `starts` to a tile matching
+/// `is_goal`
+fn cheapest_route(
+    block: &Block,
+    starts: &[(usize, usize)],
+    is_goal: impl Fn(usize, usize) -> bool,
+) -> Option<Vec<(usize, usize)>> {
+    let mut costs = [[usize::MAX; BLOCK_SIZE]; BLOCK_SIZE];
+    let mut previous = [[None; BLOCK_SIZE]; BLOCK_SIZE];
+    let mut queue = BinaryHeap::new();
+    for &(x, z) in starts {
+        if let Some(cost) = clearing_cost(&block[x][z]) {
+            costs[x][z] = cost;
+            queue.push(Reverse((cost, x, z)));
+        }
+    }
+
+    while let Some(Reverse((cost, x, z))) = queue.pop() {
+        if cost > costs[x][z] {
+            continue;
+        }
+        if is_goal(x, z) {
+            let mut route = vec![(x, z)];
+            let mut current = (x, z);
+            while let Some(tile) = previous[current.0][current.1] {
+                route.push(tile);
+                current = tile;
+            }
+            return Some(route);
+        }
+        for (nx, nz) in neighbours(x, z) {
+            if let Some(step) = clearing_cost(&block[nx][nz]) {
+                if cost + step < costs[nx][nz] {
+                    costs[nx][nz] = cost + step;
+                    previous[nx][nz] = Some((x, z));
+                    queue.push(Reverse((cost + step, nx, nz)));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Every tile that can be walked to from `starts` without clearing anything
+fn walkable_from(block: &Block, starts: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut seen = [[false; BLOCK_SIZE]; BLOCK_SIZE];
+    let mut stack: Vec<(usize, usize)> = starts
+        .iter()
+        .copied()
+        .filter(|&(x, z)| clearing_cost(&block[x][z]) == Some(0))
+        .collect();
+    let mut found = Vec::new();
+    while let Some((x, z)) = stack.pop() {
+        if seen[x][z] {
+            continue;
+        }
+        seen[x][z] = true;
+        found.push((x, z));
+        stack.extend(
+            neighbours(x, z)
+                .filter(|&(nx, nz)| !seen[nx][nz] && clearing_cost(&block[nx][nz]) == Some(0)),
+        );
+    }
+    found
+}
+
+/// Flatten a route down to the floor
+fn carve(block: &mut Block, route: &[(usize, usize)]) {
+    for &(x, z) in route {
+        let tile = &mut block[x][z];
+        tile.height = 0;
+        tile.copse = false;
+        tile.kind = tile.biome.ground();
+    }
+}
+
+/// Make sure that the player can walk across a chunk from row `entry` on its
+/// left edge to row `exit` on its right one and that every cage can be
+/// reached on the way, carving a route through the terrain where there isn't
+/// one.
+fn ensure_traversable(block: &mut Block, entry: usize, exit: usize) {
+    // Nothing can be carved through a cage, so one in the way has to go
+    for (x, z) in [(0, entry), (BLOCK_SIZE - 1, exit)] {
+        if block[x][z].item == Some(ItemType::Cage) {
+            block[x][z].item = None;
+        }
+    }
+    let start = [(0, entry)];
+    if let Some(route) = cheapest_route(block, &start, |x, z| (x, z) == (BLOCK_SIZE - 1, exit)) {
+        carve(block, &route);
+    }
+
+    for x in 0..BLOCK_SIZE {
+        for z in 0..BLOCK_SIZE {
+            if !matches!(block[x][z].item, Some(ItemType::Cage)) {
+                continue;
+            }
+            let reachable = walkable_from(block, &start);
+            let is_beside_cage = |nx: usize, nz: usize| neighbours(x, z).any(|n| n == (nx, nz));
+            if block[x][z].height <= MAX_CAGE_HEIGHT
+                && reachable.iter().any(|&(nx, nz)| is_beside_cage(nx, nz))
+            {
+                continue;
+            }
+            if let Some(route) = cheapest_route(block, &reachable, is_beside_cage) {
+                carve(block, &route);
+            }
+            block[x][z].height = block[x][z].height.min(MAX_CAGE_HEIGHT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::iproduct;
+    use std::fmt::Write;
+
+    const SNAPSHOT_SEEDS: [u32; 3] = [0, 42, 1337];
+    /// Chunks relative to the start, spread out to cover every zone
+    const SNAPSHOT_CHUNKS: [Chunk; 6] = [
+        Chunk(0, 0),
+        Chunk(1, 0),
+        Chunk(-3, 2),
+        Chunk(7, -1),
+        Chunk(12, -5),
+        Chunk(40, 0),
+    ];
+
+    fn asset(path: &str) -> String {
+        std::fs::read_to_string(format!("{}/assets/{}", env!("CARGO_MANIFEST_DIR"), path)).unwrap()
+    }
+
+    fn shipped_profile() -> GenProfile {
+        serde_json::from_str(&asset("worldgen/default.gen.json")).unwrap()
+    }
+
+    fn shipped_prefabs() -> Vec<Prefab> {
+        let mut names = PREFABS.to_vec();
+        names.sort_unstable();
+        names
+            .iter()
+            .map(|name| {
+                serde_json::from_str(&asset(&format!("prefabs/{}.prefab.json", name))).unwrap()
+            })
+            .collect()
+    }
+
+    /// One token for each tile: height, tile type, biome and then what is on
+    /// top of it
+    fn describe(block: &Block) -> String {
+        let mut text = String::new();
+        for z in 0..BLOCK_SIZE {
+            let row: Vec<String> = block
+                .iter()
+                .map(|col| {
+                    let tile = &col[z];
+                    let on_top = match (tile.item, tile.copse) {
+                        (Some(ItemType::Cage), _) => 'C',
+                        (Some(ItemType::Launcher), _) => 'L',
+                        (Some(ItemType::Bomb), _) => 'X',
+                        (None, true) => 'T',
+                        (None, false) => '.',
+                    };
+                    let kind = TileType::ALL.iter().position(|k| *k == tile.kind).unwrap();
+                    format!("{}{}{}{}", tile.height, kind, tile.biome as usize, on_top)
+                })
+                .collect();
+            writeln!(text, "{}", row.join(" ")).unwrap();
+        }
+        text
+    }
+
+    fn count(block: &Block, item: ItemType) -> usize {
+        block
+            .iter()
+            .flatten()
+            .filter(|tile| tile.item == Some(item))
+            .count()
+    }
+
+    fn check_invariants(
+        block: &Block,
+        chunk: &Chunk,
+        seed: u32,
+        max_launchers: usize,
+        label: &str,
+    ) {
+        assert!(
+            count(block, ItemType::Cage) <= 1,
+            "more than one cage in {}",
+            label
+        );
+        assert!(
+            count(block, ItemType::Launcher) <= max_launchers,
+            "more than {} launchers in {}",
+            max_launchers,
+            label
+        );
+        assert!(
+            block.iter().flatten().all(|tile| tile.height <= MAX_ELEV),
+            "tile above MAX_ELEV in {}",
+            label
+        );
+        let exit = (BLOCK_SIZE - 1, crossing_row(&chunk.offset(1, 0), seed));
+        assert!(
+            walkable_from(block, &[(0, crossing_row(chunk, seed))]).contains(&exit),
+            "no safe way across {}",
+            label
+        );
+    }
+
+    #[test]
+    fn presets_leave_room_to_get_steeper() {
+        for name in GEN_PRESETS {
+            let profile: GenProfile =
+                serde_json::from_str(&asset(&format!("worldgen/{}.gen.json", name))).unwrap();
+            assert_eq!(profile.check(), Ok(()), "{}", name);
+            assert!(
+                profile.max_elevation < MAX_ELEV,
+                "{} can't get steeper",
+                name
+            );
+        }
+        assert_eq!(GenProfile::default().check(), Ok(()));
+        let too_tall = GenProfile {
+            max_elevation: MAX_ELEV + 1,
+            ..GenProfile::default()
+        };
+        assert!(too_tall.check().is_err());
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let (profile, prefabs) = (shipped_profile(), shipped_prefabs());
+        for seed in SNAPSHOT_SEEDS {
+            for chunk in SNAPSHOT_CHUNKS {
+                assert_eq!(
+                    describe(&propogate_block(&chunk, Some(seed), &profile, &prefabs)),
+                    describe(&propogate_block(&chunk, Some(seed), &profile, &prefabs)),
+                    "seed {} {:?}",
+                    seed,
+                    chunk
+                );
+            }
+        }
+    }
+
+    /// Compare chunks against the snapshots in `tests/snapshots/world_gen`.
+    ///
+    /// Run with `UPDATE_SNAPSHOTS=1` to rewrite them after a deliberate change
+    /// to world generation.
+    #[test]
+    fn generation_matches_snapshots() {
+        let (profile, prefabs) = (shipped_profile(), shipped_prefabs());
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+        for seed in SNAPSHOT_SEEDS {
+            let mut text = String::new();
+            for chunk in SNAPSHOT_CHUNKS {
+                writeln!(text, "chunk {} {}", chunk.0, chunk.1).unwrap();
+                text.push_str(&describe(&propogate_block(
+                    &chunk,
+                    Some(seed),
+                    &profile,
+                    &prefabs,
+                )));
+            }
+
+            let path = format!(
+                "{}/tests/snapshots/world_gen/seed_{}.txt",
+                env!("CARGO_MANIFEST_DIR"),
+                seed
+            );
+            if update {
+                std::fs::write(&path, &text).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                text == expected,
+                "seed {} no longer matches {}, rerun with UPDATE_SNAPSHOTS=1 if this was intended",
+                seed,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn generated_chunks_keep_invariants() {
+        let profile = shipped_profile();
+        for seed in 0..10 {
+            for (x, z) in iproduct!(-30..30, -3..=3) {
+                let chunk = Chunk(x, z);
+                let block = propogate_block(&chunk, Some(seed), &profile, &[]);
+                // Two in the first zones, with more further out
+                let max_launchers = profile.launchers(profile.zone(&chunk));
+                let label = format!("seed {} {:?}", seed, chunk);
+                check_invariants(&block, &chunk, seed, max_launchers, &label);
+            }
+        }
+    }
+
+    #[test]
+    fn routes_meet_at_chunk_edges() {
+        let (profile, prefabs) = (shipped_profile(), shipped_prefabs());
+        for seed in 0..10 {
+            for (x, z) in iproduct!(-10..10, -2..=2) {
+                let (left, right) = (Chunk(x, z), Chunk(x + 1, z));
+                let row = crossing_row(&right, seed);
+                let left_block = propogate_block(&left, Some(seed), &profile, &prefabs);
+                let right_block = propogate_block(&right, Some(seed), &profile, &prefabs);
+                let (out, into) = (&left_block[BLOCK_SIZE - 1][row], &right_block[0][row]);
+                assert!(
+                    clearing_cost(out) == Some(0) && clearing_cost(into) == Some(0),
+                    "seed {} {:?} and {:?} don't meet",
+                    seed,
+                    left,
+                    right
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prefab_chunks_keep_invariants() {
+        let (profile, prefabs) = (shipped_profile(), shipped_prefabs());
+        for seed in 0..10 {
+            for (x, z) in iproduct!(-30..30, -3..=3) {
+                let chunk = Chunk(x, z);
+                let block = propogate_block(&chunk, Some(seed), &profile, &prefabs);
+                // A prefab replaces the generated items, so it sets its own limit
+                let max_launchers = match choose_prefab(&chunk, seed, &profile, &prefabs) {
+                    Some(prefab) => {
+                        let mut stamped = [[TileSettings::default(); BLOCK_SIZE]; BLOCK_SIZE];
+                        prefab.stamp(&mut stamped);
+                        count(&stamped, ItemType::Launcher)
+                    }
+                    None => profile.launchers(profile.zone(&chunk)),
+                };
+                let label = format!("seed {} {:?}", seed, chunk);
+                check_invariants(&block, &chunk, seed, max_launchers, &label);
+            }
+        }
+    }
+
+    #[test]
+    fn exported_chunks_come_back_as_fixed_levels() {
+        let (profile, prefabs) = (shipped_profile(), shipped_prefabs());
+        let chunk = Chunk(7, -1);
+        let mut block = propogate_block(&chunk, Some(42), &profile, &prefabs);
+        block[5][5].copse = !block[5][5].copse;
+        // Tiles on the carved route come back as they were edited too
+        let (entry, exit) = (
+            crossing_row(&chunk, 42),
+            crossing_row(&chunk.offset(1, 0), 42),
+        );
+        block[0][entry].height = 2;
+        block[0][entry].kind = TileType::Sandstone;
+        block[BLOCK_SIZE - 1][exit].kind = TileType::Ice;
+        block[BLOCK_SIZE - 1][exit].copse = true;
+        let level = Prefab::from_block("level", &block, Some(chunk)).unwrap();
+        let json = serde_json::to_string(&level).unwrap();
+        let level: Prefab = serde_json::from_str(&json).unwrap();
+        assert_eq!(level.rows.len(), BLOCK_SIZE);
+
+        let mut with_level = prefabs.clone();
+        with_level.push(level);
+        let rebuilt = propogate_block(&chunk, Some(42), &profile, &with_level);
+        assert_eq!(describe(&rebuilt), describe(&block));
+        // The level only goes in its own chunk
+        assert_eq!(
+            describe(&propogate_block(
+                &Chunk(8, -1),
+                Some(7),
+                &profile,
+                &with_level
+            )),
+            describe(&propogate_block(&Chunk(8, -1), Some(7), &profile, &prefabs))
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! A headless way of looking at generated chunks, for reviewing world
+//! generation changes without playing through them.
+//!
+//! `flocked-and-loaded worldgen --seed 4 --x -2..3 --z 0..1` prints an ASCII
+//! map of the chunks, and adding `--png map.png` writes a top-down image
+//! instead.
+
+use crate::chunk::Chunk;
+use crate::chunk_manager_plugin::{Block, BLOCK_SIZE, MAX_ELEV};
+use crate::item_plugin::ItemType;
+use crate::share::{type_to_colour, TileSettings, TileType};
+use crate::world_gen::{propogate_block, GenProfile, Prefab};
+use std::ops::Range;
+
+/// How many pixels wide each tile is in a PNG
+const PIXELS_PER_TILE: usize = 4;
+
+const USAGE: &str = "usage: flocked-and-loaded worldgen [--seed N] [--x FROM..TO] [--z FROM..TO] \
+[--profile NAME] [--png PATH]
+
+Chunk ranges are relative to the start chunk and exclude TO.
+The ASCII map has x running across and z running down, with
+  . flat ground   1-5 raised ground   T trees   C cage   L launcher
+  ~ water   ! lava   ^ spikes";
+
+struct Options {
+    seed: u32,
+    x: Range<i32>,
+    z: Range<i32>,
+    profile: String,
+    png: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            seed: 0,
+            x: 0..3,
+            z: 0..1,
+            profile: "default".to_string(),
+            png: None,
+        }
+    }
+}
+
+/// Run the generator with the arguments that follow `worldgen`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = parse_args(args)?;
+    let profile = load_profile(&options.profile)?;
+    let prefabs = load_prefabs()?;
+
+    // Chunks are laid out in a grid with x across and z down
+    let chunks: Vec<Vec<Block>> = options
+        .z
+        .clone()
+        .map(|cz| {
+            options
+                .x
+                .clone()
+                .map(|cx| propogate_block(&Chunk(cx, cz), Some(options.seed), &profile, &prefabs))
+                .collect()
+        })
+        .collect();
+
+    match options.png {
+        Some(path) => write_png(&chunks, &path),
+        None => {
+            print!("{}", ascii_map(&chunks));
+            Ok(())
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Err(USAGE.to_string());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value\n\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--seed" => {
+                options.seed = value
+                    .parse()
+                    .map_err(|_| format!("{} is not a seed", value))?
+            }
+            "--x" => options.x = parse_range(value)?,
+            "--z" => options.z = parse_range(value)?,
+            "--profile" => options.profile = value.clone(),
+            "--png" => options.png = Some(value.clone()),
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+/// Parse `FROM..TO`, or a single chunk on its own
+fn parse_range(value: &str) -> Result<Range<i32>, String> {
+    let bad = || format!("{} is not a chunk range", value);
+    let range = match value.split_once("..") {
+        Some((from, to)) => from.parse().map_err(|_| bad())?..to.parse().map_err(|_| bad())?,
+        None => {
+            let chunk: i32 = value.parse().map_err(|_| bad())?;
+            chunk..chunk + 1
+        }
+    };
+    if range.is_empty() {
+        Err(bad())
+    } else {
+        Ok(range)
+    }
+}
+
+fn load_profile(name: &str) -> Result<GenProfile, String> {
+    let path = format!("assets/worldgen/{}.gen.json", name);
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let profile: GenProfile =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    profile.check().map_err(|e| format!("{}: {}", path, e))?;
+    Ok(profile)
+}
+
+/// Load every prefab in the folder, including those exported from the level
+/// editor, just as the game does when it runs natively
+fn load_prefabs() -> Result<Vec<Prefab>, String> {
+    let dir = "assets/prefabs";
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir, e))?
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            Some(file_name.strip_suffix(".prefab.json")?.to_string())
+        })
+        .collect();
+    // Sorted the same way as in game so that the same seed gives the same map
+    names.sort_unstable();
+    names
+        .into_iter()
+        .map(|name| {
+            let path = format!("{}/{}.prefab.json", dir, name);
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+        })
+        .collect()
+}
+
+fn tile_char(tile: &TileSettings) -> char {
+    match (tile.item, tile.copse, tile.height) {
+        (Some(ItemType::Cage), _, _) => 'C',
+        (Some(ItemType::Launcher), _, _) => 'L',
+        (_, true, _) => 'T',
+        _ if tile.kind == TileType::Water => '~',
+        _ if tile.kind == TileType::Lava => '!',
+        _ if tile.kind == TileType::Spikes => '^',
+        (_, _, 0) => '.',
+        (_, _, height) => char::from_digit(height as u32, 10).unwrap_or('#'),
+    }
+}
+
+fn ascii_map(chunks: &[Vec<Block>]) -> String {
+    let mut map = String::new();
+    for row in chunks {
+        for z in 0..BLOCK_SIZE {
+            for block in row {
+                map.extend((0..BLOCK_SIZE).map(|x| tile_char(&block[x][z])));
+            }
+            map.push('\n');
+        }
+    }
+    map
+}
+
+/// The colour of a tile in the heatmap, taken from its in-game colour and
+/// brightened by its height
+fn tile_colour(tile: &TileSettings) -> [u8; 3] {
+    let [r, g, b, _] = match (tile.item, tile.copse) {
+        (Some(ItemType::Cage), _) => [1., 0.85, 0., 1.],
+        (Some(ItemType::Launcher), _) => [0.9, 0.1, 0.1, 1.],
+        (_, true) => [0.02, 0.25, 0.05, 1.],
+        _ => type_to_colour(&tile.kind).as_rgba_f32(),
+    };
+    let shade = 0.6 + 0.4 * tile.height as f32 / MAX_ELEV as f32;
+    [r, g, b].map(|c| ((c * shade).clamp(0., 1.) * 255.) as u8)
+}
+
+fn write_png(chunks: &[Vec<Block>], path: &str) -> Result<(), String> {
+    let width = chunks[0].len() * BLOCK_SIZE * PIXELS_PER_TILE;
+    let height = chunks.len() * BLOCK_SIZE * PIXELS_PER_TILE;
+    let image = image::RgbImage::from_fn(width as u32, height as u32, |px, py| {
+        let (tx, tz) = (px as usize / PIXELS_PER_TILE, py as usize / PIXELS_PER_TILE);
+        let block = &chunks[tz / BLOCK_SIZE][tx / BLOCK_SIZE];
+        image::Rgb(tile_colour(&block[tx % BLOCK_SIZE][tz % BLOCK_SIZE]))
+    });
+    image.save(path).map_err(|e| format!("{}: {}", path, e))?;
+    println!("wrote {}", path);
+    Ok(())
+}